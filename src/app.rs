@@ -1,13 +1,25 @@
-use std::{ops::Add, rc::Rc, cell::RefCell};
+use std::{rc::Rc, cell::RefCell, io, path::PathBuf, sync::{Arc, mpsc}, thread};
 
 use eframe::{egui, CreationContext};
-use chrono::{Datelike, NaiveDate, Weekday, IsoWeek, Duration, Days, Local};
+use directories_next::UserDirs;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Weekday, IsoWeek, Duration, Days};
 use egui::{ColorImage, TextureOptions};
-use crate::{timetable::{Timetable, Event, TimetableGetter, GetTimetableError}, config::{ConfigStore, Config}, events_table::EventsTable};
+use ktu_timetable::{timetable::{Timetable, Event, EventCategory, TimetableGetter, GetTimetableError, SearchField, normalize_vidko, is_valid_vidko}, config::{ConfigStore, Config, Profile, WeekRollover, HttpConfig, LoadConfigError, DEFAULT_PROFILE, data_dir, write_atomic}, user_data::{UserData, UserDataStore, PersonalEvent, Task, Attendance}, ics::tasks_to_ics, keybindings::{Action, Keybindings}, i18n::{Language, Strings}, statistics::Statistics};
+use crate::{events_table::{EventsTable, get_category_bg, get_event_bg}, semester_overview::SemesterOverview, shortcuts::{Shortcut, is_triggered}};
 
-use crate::utils::load_image_from_memory;
+use ktu_timetable::utils::{week_monday, same_week, get_current_week, get_current_day, WeekScroll};
+use ktu_timetable::timezone::vilnius_now;
 
-const MAX_FUTURE_WEEKS: u64 = 4 * 12;
+fn load_image_from_memory(image_data: &[u8]) -> Result<ColorImage, image::ImageError> {
+    let image = image::load_from_memory(image_data)?;
+    let size = [image.width() as _, image.height() as _];
+    let image_buffer = image.to_rgba8();
+    let pixels = image_buffer.as_flat_samples();
+    Ok(ColorImage::from_rgba_unmultiplied(
+        size,
+        pixels.as_slice(),
+    ))
+}
 
 lazy_static! {
     pub static ref BREAK_IMAGE: ColorImage = load_image_from_memory(include_bytes!("../assets/break-area.png"))
@@ -18,114 +30,1435 @@ struct AppAssets {
     break_texture: egui::TextureHandle
 }
 
-pub struct MainApp {
-    shown_week: IsoWeek,
-    shown_events: Vec<Event>,
+pub struct MainApp {
+    shown_week: IsoWeek,
+    shown_events: Vec<Event>,
+
+    timetable_getter: Arc<dyn TimetableGetter>,
+    // Settings the getter was last given, to know when they change
+    applied_http: Option<HttpConfig>,
+    http_error: Option<String>,
+    pub timetable: Option<Timetable>,
+    // Download of the active profile's timetable, when one is in progress
+    refresh: Option<PendingTimetable>,
+    // Why the last download failed, the previous or cached timetable is shown meanwhile
+    refresh_error: Option<String>,
+    // Downloads which couldn't be kept for showing while offline
+    cache_error: Option<String>,
+    // Increased whenever `timetable` is replaced, so that windows know when their cached results are stale
+    timetable_generation: u64,
+
+    config_store: Box<dyn ConfigStore>,
+    config: Option<Config>,
+    // What was last saved, to know when the config needs to be saved again
+    saved_config: Option<Config>,
+
+    user_data_store: Box<dyn UserDataStore>,
+    user_data: UserData,
+    saved_user_data: UserData,
+
+    assets: Option<AppAssets>,
+
+    settings_open: bool,
+    shortcuts_open: bool,
+    // Action which is waiting for a new shortcut to be pressed
+    rebinding: Option<Action>,
+    new_profile_name: String,
+    // Delete was pressed and is waiting to be confirmed
+    deleting_profile: bool,
+    new_extra_vidko: String,
+    // Shown until dismissed, instead of silently starting with a default config
+    config_error: Option<String>,
+    // Settings or profiles which couldn't be read and were left out
+    config_warning: Option<String>,
+    user_data_error: Option<String>,
+    save_error: Option<String>,
+    // Saves which failed, they are tried again before the window closes
+    config_unsaved: bool,
+    user_data_unsaved: bool,
+    // Closing was stopped once to show that saving still fails, the next close goes through
+    close_blocked: bool,
+
+    screen: Option<Rc<RefCell<dyn Screen>>>
+}
+
+// Timetables downloaded and merged on another thread, so that slow requests and their retries don't freeze the window
+struct PendingTimetable {
+    vidkos: Vec<String>,
+    receiver: mpsc::Receiver<Result<Timetable, GetTimetableError>>
+}
+
+impl PendingTimetable {
+    fn start(getter: &Arc<dyn TimetableGetter>, vidkos: Vec<String>, ctx: &egui::Context) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let getter = getter.clone();
+        let thread_vidkos = vidkos.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            // Fails only when the result isn't wanted anymore
+            let _ = sender.send(getter.get_merged(&thread_vidkos));
+            ctx.request_repaint();
+        });
+        Self { vidkos, receiver }
+    }
+
+    // None while still downloading
+    fn poll(&self) -> Option<Result<Timetable, GetTimetableError>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err(GetTimetableError::RequestFailed("Download was interrupted".into())))
+        }
+    }
+}
+
+#[inline]
+fn is_weekend(time: NaiveDate) -> bool {
+    let day = time.weekday();
+    return day == Weekday::Sat || day == Weekday::Sun;
+}
+
+fn week_label(week: IsoWeek, strings: &Strings) -> String {
+    let monday = week_monday(week);
+    let friday = monday + Duration::days(4);
+    format!(
+        "{:02} {} ({} - {})",
+        week.week(),
+        strings.week_short,
+        monday.format(strings.short_date_format),
+        friday.format(strings.short_date_format)
+    )
+}
+
+fn duration_label(minutes: i64, strings: &Strings) -> String {
+    format!("{} {} {} {}", minutes / 60, strings.hours_short, minutes % 60, strings.minutes_short)
+}
+
+trait Screen {
+    fn show(&mut self, app: &mut MainApp, ctx: &egui::Context);
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum View {
+    Week,
+    Day,
+    Agenda,
+    Overview,
+    Statistics
+}
+
+// `add_tools` is used to add screen specific buttons after the tabs
+fn show_view_tabs(app: &mut MainApp, ctx: &egui::Context, current: View, add_tools: impl FnOnce(&mut MainApp, &mut egui::Ui)) {
+    let strings = app.strings();
+    egui::TopBottomPanel::top("view-tabs").show(ctx, |ui| {
+        ui.horizontal_wrapped(|ui| {
+            let views = [
+                (View::Week, strings.week_view),
+                (View::Day, strings.day_view),
+                (View::Agenda, strings.agenda_view),
+                (View::Overview, strings.overview_view),
+                (View::Statistics, strings.statistics_view)
+            ];
+            for (view, label) in views {
+                if ui.selectable_label(current == view, label).clicked() && current != view {
+                    app.switch_to_view(view);
+                }
+            }
+
+            ui.separator();
+            show_filters_menu(app, ui);
+            add_tools(app, ui);
+
+            ui.separator();
+            if app.refresh.is_some() {
+                ui.spinner();
+            }
+            if ui.button("⚙").on_hover_text(Action::OpenSettings.label(strings)).clicked() {
+                app.settings_open = true;
+            }
+            if ui.button("?").on_hover_text(Action::ShowShortcuts.label(strings)).clicked() {
+                app.shortcuts_open = !app.shortcuts_open;
+            }
+        });
+    });
+}
+
+fn show_week_picker(app: &mut MainApp, ui: &mut egui::Ui) {
+    let strings = app.strings();
+    let (first_week, last_week) = app.week_range();
+    // Weeks are picked by their mondays, see `same_week`
+    let shown_monday = week_monday(app.shown_week);
+    let mut selected_monday = shown_monday;
+
+    egui::ComboBox::from_id_source("week-picker")
+        .selected_text(week_label(app.shown_week, strings))
+        .show_ui(ui, |ui| {
+            let mut monday = week_monday(first_week);
+            while monday <= week_monday(last_week) {
+                ui.selectable_value(&mut selected_monday, monday, week_label(monday.iso_week(), strings));
+                monday = monday + Duration::days(7);
+            }
+        });
+
+    if selected_monday != shown_monday {
+        app.set_shown_week(selected_monday.iso_week());
+    }
+}
+
+// Colours which modules can be given in the filters menu
+const MODULE_COLOURS: [EventCategory; 5] = [
+    EventCategory::Default,
+    EventCategory::Yellow,
+    EventCategory::Green,
+    EventCategory::Red,
+    EventCategory::Blue
+];
+
+fn show_filters_menu(app: &mut MainApp, ui: &mut egui::Ui) {
+    let module_names = match &app.timetable {
+        Some(timetable) => timetable.module_names(),
+        None => return
+    };
+
+    ui.menu_button(app.strings().filters, |ui| {
+        for module in &module_names {
+            ui.horizontal(|ui| {
+                let mut shown = app.profile()
+                    .map_or(true, |profile| !profile.hidden_modules.contains(module));
+                if ui.checkbox(&mut shown, module.as_str()).changed() {
+                    app.set_module_hidden(module, !shown);
+                }
+
+                // Clicking the picked colour again goes back to the one from KTU
+                let picked = app.profile().and_then(|profile| profile.module_colours.get(module).copied());
+                for colour in MODULE_COLOURS {
+                    let mut button = egui::Button::new("  ").small().fill(get_category_bg(colour));
+                    if picked == Some(colour) {
+                        button = button.stroke((2.0, ui.visuals().strong_text_color()));
+                    }
+                    if ui.add(button).clicked() {
+                        app.set_module_colour(module, if picked == Some(colour) { None } else { Some(colour) });
+                    }
+                }
+            });
+        }
+    });
+}
+
+struct SearchWindow {
+    open: bool,
+    query: String,
+    field: SearchField,
+    results: Vec<Event>,
+    // `MainApp::timetable_generation` which the results were searched in
+    searched_generation: u64
+}
+
+impl Default for SearchWindow {
+    fn default() -> Self {
+        Self {
+            open: false,
+            query: String::new(),
+            field: SearchField::Any,
+            results: vec![],
+            searched_generation: 0
+        }
+    }
+}
+
+impl SearchWindow {
+    fn field_label(field: SearchField, strings: &Strings) -> &'static str {
+        strings.search_fields[field as usize]
+    }
+
+    fn show(&mut self, app: &mut MainApp, ctx: &egui::Context) {
+        use egui::*;
+
+        let strings = app.strings();
+        let mut open = self.open;
+        egui::Window::new(Action::Search.label(strings))
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                let mut changed = false;
+                ui.horizontal(|ui| {
+                    let response = ui.text_edit_singleline(&mut self.query);
+                    if response.changed() {
+                        changed = true;
+                    }
+                    if self.results.is_empty() && self.query.is_empty() {
+                        response.request_focus();
+                    }
+
+                    ComboBox::from_id_source("search-field")
+                        .selected_text(Self::field_label(self.field, strings))
+                        .show_ui(ui, |ui| {
+                            for field in [SearchField::Any, SearchField::Module, SearchField::Code, SearchField::Room, SearchField::Lecturer, SearchField::Description] {
+                                if ui.selectable_value(&mut self.field, field, Self::field_label(field, strings)).changed() {
+                                    changed = true;
+                                }
+                            }
+                        });
+                });
+
+                if changed || self.searched_generation != app.timetable_generation {
+                    self.searched_generation = app.timetable_generation;
+                    self.results = match &app.timetable {
+                        Some(timetable) => timetable.search(&self.query, self.field),
+                        None => vec![],
+                    };
+                }
+
+                if self.results.is_empty() && !self.query.trim().is_empty() {
+                    ui.label(strings.nothing_found);
+                }
+
+                ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for event in &self.results {
+                            let module_name = event.module_name.as_ref().unwrap_or(&event.summary);
+                            let label = format!(
+                                "{} {}  {}  {}",
+                                event.start.format(strings.date_format),
+                                event.time_label(),
+                                module_name,
+                                event.location
+                            );
+                            let is_shown = same_week(event.start.iso_week(), app.shown_week);
+                            if ui.selectable_label(is_shown, label).clicked() {
+                                app.set_shown_week(event.start.iso_week());
+                            }
+                        }
+                    });
+            });
+        self.open = open;
+    }
+}
+
+struct OtherTimetable {
+    vidko: String,
+    timetable: Timetable,
+    selected: bool
+}
+
+// Timetables which are selected in the free time window, merged into one
+struct MergedTimetable {
+    // Generation of the own timetable, whether it's included and the selected vidko codes
+    key: (u64, bool, Vec<String>),
+    timetable: Timetable,
+    rooms: Vec<String>
+}
+
+struct FreeTimeWindow {
+    open: bool,
+    vidko_textfield: String,
+    get_error: Option<GetTimetableError>,
+    pending: Option<PendingTimetable>,
+
+    include_own: bool,
+    others: Vec<OtherTimetable>,
+    room: Option<String>,
+    // Merging clones every event, so it's only done again when the selection changes
+    merged: Option<MergedTimetable>,
+
+    day_start_hour: u32,
+    day_end_hour: u32,
+    min_minutes: i64
+}
+
+impl Default for FreeTimeWindow {
+    fn default() -> Self {
+        Self {
+            open: false,
+            vidko_textfield: String::new(),
+            get_error: None,
+            pending: None,
+            include_own: true,
+            others: vec![],
+            room: None,
+            merged: None,
+            day_start_hour: 8,
+            day_end_hour: 20,
+            min_minutes: 30
+        }
+    }
+}
+
+impl FreeTimeWindow {
+    fn add_timetable(&mut self, app: &MainApp, ctx: &egui::Context) {
+        let vidko = normalize_vidko(&self.vidko_textfield);
+        if !is_valid_vidko(&vidko) || self.others.iter().any(|other| other.vidko == vidko) {
+            return;
+        }
+
+        self.pending = Some(PendingTimetable::start(&app.timetable_getter, vec![vidko], ctx));
+    }
+
+    fn poll_pending(&mut self) {
+        let result = match self.pending.as_ref().and_then(PendingTimetable::poll) {
+            Some(result) => result,
+            None => return
+        };
+        let vidko = self.pending.take().unwrap().vidkos.remove(0);
+
+        match result {
+            Ok(timetable) => {
+                self.others.push(OtherTimetable { vidko, timetable, selected: true });
+                self.vidko_textfield.clear();
+                self.get_error = None;
+            },
+            Err(e) => {
+                self.get_error = Some(e);
+            }
+        }
+    }
+
+    fn update_merged(&mut self, app: &MainApp) {
+        let selected_vidkos = self.others.iter()
+            .filter(|other| other.selected)
+            .map(|other| other.vidko.clone())
+            .collect();
+        let key = (app.timetable_generation, self.include_own, selected_vidkos);
+        if self.merged.as_ref().map_or(false, |merged| merged.key == key) {
+            return;
+        }
+
+        let own = if self.include_own { app.timetable.as_ref() } else { None };
+        let selected = self.others.iter()
+            .filter(|other| other.selected)
+            .map(|other| &other.timetable);
+        let timetable = Timetable::merge(own.into_iter().chain(selected));
+        let rooms = timetable.rooms();
+        self.merged = Some(MergedTimetable { key, timetable, rooms });
+    }
+
+    fn show(&mut self, app: &mut MainApp, ctx: &egui::Context) {
+        use egui::*;
+
+        self.poll_pending();
+        let strings = app.strings();
+        let mut open = self.open;
+        egui::Window::new(strings.free_time)
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                let vidko = normalize_vidko(&self.vidko_textfield);
+                let is_valid = is_valid_vidko(&vidko);
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}: ", strings.vidko));
+                    if ui.text_edit_singleline(&mut self.vidko_textfield).changed() {
+                        self.get_error = None;
+                    }
+                    if ui.add_enabled(is_valid && self.pending.is_none(), Button::new(strings.add)).clicked() {
+                        self.add_timetable(app, ctx);
+                    }
+                    if self.pending.is_some() {
+                        ui.spinner();
+                    }
+                });
+                if !vidko.is_empty() && !is_valid {
+                    ui.colored_label(Color32::RED, strings.vidko_format_hint);
+                }
+                if self.get_error.is_some() {
+                    ui.colored_label(Color32::RED, strings.invalid_vidko);
+                }
+
+                if let Some(vidko) = app.vidko() {
+                    ui.checkbox(&mut self.include_own, format!("{} ({})", vidko, strings.mine));
+                }
+                let mut removed = None;
+                for (i, other) in self.others.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut other.selected, other.vidko.as_str());
+                        if ui.small_button("✖").clicked() {
+                            removed = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = removed {
+                    self.others.remove(i);
+                }
+
+                ui.horizontal(|ui| {
+                    let hours_suffix = format!(" {}", strings.hours_short);
+                    ui.label(strings.from);
+                    ui.add(DragValue::new(&mut self.day_start_hour).clamp_range(0..=22).suffix(hours_suffix.as_str()));
+                    ui.label(strings.to);
+                    ui.add(DragValue::new(&mut self.day_end_hour).clamp_range(1..=23).suffix(hours_suffix.as_str()));
+                    ui.label(strings.at_least);
+                    ui.add(DragValue::new(&mut self.min_minutes).clamp_range(0..=240).suffix(format!(" {}", strings.minutes_short)));
+                });
+
+                self.update_merged(app);
+                let merged = self.merged.as_ref().unwrap();
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}: ", strings.room));
+                    ComboBox::from_id_source("free-time-room")
+                        .selected_text(self.room.as_deref().unwrap_or("-"))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.room, None, "-");
+                            for room in &merged.rooms {
+                                ui.selectable_value(&mut self.room, Some(room.clone()), room.as_str());
+                            }
+                        });
+                });
+                ui.separator();
+
+                let from = week_monday(app.shown_week);
+                let to = from + Duration::days(4);
+                let day_start = NaiveTime::from_hms_opt(self.day_start_hour, 0, 0).unwrap();
+                let day_end = NaiveTime::from_hms_opt(self.day_end_hour.max(self.day_start_hour + 1), 0, 0).unwrap();
+                let min_length = Duration::minutes(self.min_minutes);
+                let slots = match &self.room {
+                    Some(room) => merged.timetable.free_slots_in_room(room, from, to, day_start, day_end, min_length),
+                    None => merged.timetable.free_slots(from, to, day_start, day_end, min_length)
+                };
+
+                if slots.is_empty() {
+                    ui.label(strings.no_free_time);
+                }
+                ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for slot in &slots {
+                            let length = slot.end_time - slot.start_time;
+                            ui.label(format!(
+                                "{}  {} - {}  ({})",
+                                slot.date.format(strings.date_format),
+                                slot.start_time.format("%H:%M"),
+                                slot.end_time.format("%H:%M"),
+                                duration_label(length.num_minutes(), strings)
+                            ));
+                        }
+                    });
+            });
+        self.open = open;
+    }
+}
+
+// How times are typed into the event editor
+const EDITOR_TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+// Creates, edits and deletes personal events. Official events can't be changed, only a note can be attached to them.
+#[derive(Default)]
+struct EventEditor {
+    open: bool,
+    // Personal event which is being edited, None when creating a new one
+    personal_id: Option<u64>,
+    official_event: Option<Event>,
+
+    title: String,
+    start: String,
+    end: String,
+    location: String,
+    description: String,
+    note: String,
+    invalid_time: bool
+}
+
+impl EventEditor {
+    fn open_new(&mut self, day: NaiveDate) {
+        *self = Self {
+            open: true,
+            start: day.and_hms_opt(9, 0, 0).unwrap().format(EDITOR_TIME_FORMAT).to_string(),
+            end: day.and_hms_opt(10, 30, 0).unwrap().format(EDITOR_TIME_FORMAT).to_string(),
+            ..Self::default()
+        };
+    }
+
+    fn open_event(&mut self, app: &MainApp, event: &Event) {
+        *self = match event.personal_id.and_then(|id| app.user_data.event(id)) {
+            Some(personal) => Self {
+                open: true,
+                personal_id: Some(personal.id),
+                title: personal.title.clone(),
+                start: personal.start.format(EDITOR_TIME_FORMAT).to_string(),
+                end: personal.end.format(EDITOR_TIME_FORMAT).to_string(),
+                location: personal.location.clone(),
+                description: personal.description.clone(),
+                ..Self::default()
+            },
+            None => Self {
+                open: true,
+                official_event: Some(event.clone()),
+                note: event.note.clone().unwrap_or_default(),
+                ..Self::default()
+            }
+        };
+    }
+
+    fn parse_times(&self) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        let start = NaiveDateTime::parse_from_str(self.start.trim(), EDITOR_TIME_FORMAT).ok()?;
+        let end = NaiveDateTime::parse_from_str(self.end.trim(), EDITOR_TIME_FORMAT).ok()?;
+        if end <= start {
+            return None;
+        }
+        Some((start, end))
+    }
+
+    fn show_note_editor(&mut self, app: &mut MainApp, ui: &mut egui::Ui, event: &Event) -> bool {
+        let strings = app.strings();
+        ui.strong(event.module_label());
+        ui.label(format!("{} {}", event.start.format(strings.date_format), event.time_label()));
+        ui.weak(event.location.as_str());
+
+        // Attendance can only be marked once the event is over
+        if event.end <= vilnius_now() {
+            ui.separator();
+            ui.label(strings.attendance);
+            let mut attendance = app.user_data.attendance(event);
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut attendance, None, strings.not_marked);
+                for option in Attendance::ALL {
+                    ui.selectable_value(&mut attendance, Some(option), option.label(strings));
+                }
+            });
+            if attendance != app.user_data.attendance(event) {
+                app.user_data.set_attendance(event, attendance);
+                app.update_shown_events();
+            }
+        }
+
+        ui.separator();
+        ui.label(strings.note);
+        ui.text_edit_multiline(&mut self.note);
+        if ui.button(strings.save).clicked() {
+            app.user_data.set_note(event, &self.note);
+            app.update_shown_events();
+            return true;
+        }
+        false
+    }
+
+    fn show_personal_editor(&mut self, app: &mut MainApp, ui: &mut egui::Ui) -> bool {
+        let strings = app.strings();
+        egui::Grid::new("event-editor-fields").num_columns(2).show(ui, |ui| {
+            ui.label(strings.title);
+            ui.text_edit_singleline(&mut self.title);
+            ui.end_row();
+
+            ui.label(strings.from);
+            ui.text_edit_singleline(&mut self.start);
+            ui.end_row();
+
+            ui.label(strings.to);
+            ui.text_edit_singleline(&mut self.end);
+            ui.end_row();
+
+            ui.label(strings.location);
+            ui.text_edit_singleline(&mut self.location);
+            ui.end_row();
+
+            ui.label(strings.description);
+            ui.text_edit_multiline(&mut self.description);
+            ui.end_row();
+        });
+
+        if self.invalid_time {
+            ui.colored_label(egui::Color32::RED, strings.invalid_event_time);
+        }
+
+        let mut done = false;
+        ui.horizontal(|ui| {
+            let can_save = !self.title.trim().is_empty();
+            if ui.add_enabled(can_save, egui::Button::new(strings.save)).clicked() {
+                match self.parse_times() {
+                    Some((start, end)) => {
+                        app.user_data.save_event(PersonalEvent {
+                            id: self.personal_id.unwrap_or(0),
+                            title: self.title.trim().to_string(),
+                            start,
+                            end,
+                            location: self.location.trim().to_string(),
+                            description: self.description.trim().to_string()
+                        });
+                        app.update_shown_events();
+                        done = true;
+                    },
+                    None => self.invalid_time = true
+                }
+            }
+
+            if let Some(id) = self.personal_id {
+                if ui.button(strings.delete).clicked() {
+                    app.user_data.remove_event(id);
+                    app.update_shown_events();
+                    done = true;
+                }
+            }
+        });
+        done
+    }
+
+    fn show(&mut self, app: &mut MainApp, ctx: &egui::Context) {
+        let strings = app.strings();
+        let title = if self.official_event.is_some() {
+            strings.note
+        } else if self.personal_id.is_some() {
+            strings.edit_event
+        } else {
+            strings.new_event
+        };
+
+        let mut open = self.open;
+        let mut done = false;
+        egui::Window::new(title)
+            .id(egui::Id::new("event-editor"))
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                done = match self.official_event.clone() {
+                    Some(event) => self.show_note_editor(app, ui, &event),
+                    None => self.show_personal_editor(app, ui)
+                };
+            });
+        self.open = open && !done;
+    }
+}
+
+// Where exported calendars are saved, the downloads folder if there is one
+fn export_directory() -> PathBuf {
+    UserDirs::new()
+        .and_then(|dirs| dirs.download_dir().map(|dir| dir.to_path_buf()))
+        .unwrap_or_else(data_dir)
+}
+
+// Never overwrites an earlier export, picks the first free name out of ktu-tasks.ics, ktu-tasks-1.ics, ...
+fn export_tasks(tasks: &[Task]) -> io::Result<PathBuf> {
+    let directory = export_directory();
+    let mut filename = directory.join("ktu-tasks.ics");
+    let mut index = 1;
+    while filename.exists() {
+        filename = directory.join(format!("ktu-tasks-{}.ics", index));
+        index += 1;
+    }
+    write_atomic(&filename, tasks_to_ics(tasks).as_bytes())?;
+    Ok(filename)
+}
+
+#[derive(Default)]
+struct TasksWindow {
+    open: bool,
+    module: String,
+    title: String,
+    due: String,
+    invalid_due: bool,
+    export_message: Option<String>
+}
+
+impl TasksWindow {
+    fn show_task_list(&mut self, app: &mut MainApp, ui: &mut egui::Ui) {
+        let strings = app.strings();
+        if app.user_data.tasks.is_empty() {
+            ui.label(strings.no_tasks);
+            return;
+        }
+
+        let mut toggled = None;
+        let mut removed = None;
+        egui::Grid::new("tasks").striped(true).show(ui, |ui| {
+            for task in &app.user_data.tasks {
+                let mut done = task.done;
+                if ui.checkbox(&mut done, "").changed() {
+                    toggled = Some((task.id, done));
+                }
+                ui.monospace(task.due.format(EDITOR_TIME_FORMAT).to_string());
+                ui.label(task.module.as_str());
+                ui.label(task.title.as_str());
+                if ui.small_button("🗑").on_hover_text(strings.delete).clicked() {
+                    removed = Some(task.id);
+                }
+                ui.end_row();
+            }
+        });
+
+        if let Some((id, done)) = toggled {
+            app.user_data.set_task_done(id, done);
+        }
+        if let Some(id) = removed {
+            app.user_data.remove_task(id);
+        }
+    }
+
+    fn show_new_task(&mut self, app: &mut MainApp, ui: &mut egui::Ui) {
+        let strings = app.strings();
+        let modules = match &app.timetable {
+            Some(timetable) => timetable.module_names(),
+            None => vec![]
+        };
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("task-module")
+                .selected_text(self.module.as_str())
+                .show_ui(ui, |ui| {
+                    for module in modules {
+                        let label = module.clone();
+                        ui.selectable_value(&mut self.module, module, label);
+                    }
+                });
+            ui.add(egui::TextEdit::singleline(&mut self.title).hint_text(strings.title));
+            ui.label(strings.due);
+            ui.add(egui::TextEdit::singleline(&mut self.due).desired_width(120.0));
+
+            let can_add = !self.module.is_empty() && !self.title.trim().is_empty();
+            if ui.add_enabled(can_add, egui::Button::new(strings.add)).clicked() {
+                match NaiveDateTime::parse_from_str(self.due.trim(), EDITOR_TIME_FORMAT) {
+                    Ok(due) => {
+                        app.user_data.add_task(Task {
+                            id: 0,
+                            module_code: app.module_code(&self.module),
+                            module: self.module.clone(),
+                            title: self.title.trim().to_string(),
+                            due,
+                            done: false
+                        });
+                        self.title.clear();
+                        self.invalid_due = false;
+                    },
+                    Err(_) => self.invalid_due = true
+                }
+            }
+        });
+
+        if self.invalid_due {
+            ui.colored_label(egui::Color32::RED, strings.invalid_event_time);
+        }
+    }
+
+    fn show(&mut self, app: &mut MainApp, ctx: &egui::Context) {
+        let strings = app.strings();
+        if self.due.is_empty() {
+            self.due = app.current_day().and_hms_opt(23, 59, 0).unwrap().format(EDITOR_TIME_FORMAT).to_string();
+        }
+
+        let mut open = self.open;
+        egui::Window::new(strings.tasks)
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                self.show_task_list(app, ui);
+                ui.separator();
+                self.show_new_task(app, ui);
+                ui.separator();
+
+                if ui.button(strings.export_ics).clicked() {
+                    self.export_message = Some(match export_tasks(&app.user_data.tasks) {
+                        Ok(filename) => format!("{} {}", strings.exported_to, filename.display()),
+                        Err(e) => e.to_string()
+                    });
+                }
+                if let Some(message) = &self.export_message {
+                    ui.label(message.as_str());
+                }
+            });
+        self.open = open;
+    }
+}
+
+fn show_week_navigation(app: &mut MainApp, ctx: &egui::Context) {
+    use egui::*;
+
+    let strings = app.strings();
+    egui::TopBottomPanel::top("week-navigation").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            if ui.add_enabled(app.can_show_week(app.shifted_week(-1)), Button::new("◀")).clicked() {
+                app.shift_shown_week(-1);
+            }
+            if ui.button(strings.today).clicked() {
+                app.set_shown_week(app.current_week());
+            }
+            if ui.add_enabled(app.can_show_week(app.shifted_week(1)), Button::new("▶")).clicked() {
+                app.shift_shown_week(1);
+            }
+            show_week_picker(app, ui);
+
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                let monday = week_monday(app.shown_week);
+                let friday = monday + Duration::days(4);
+                ui.label(format!(
+                    "{}-W{:02}  {} - {}",
+                    app.shown_week.year(),
+                    app.shown_week.week(),
+                    monday.format(strings.date_format),
+                    friday.format(strings.date_format)
+                ));
+            });
+        });
+    });
+}
+
+#[derive(Default)]
+struct MainScreen {
+    search: SearchWindow,
+    free_time: FreeTimeWindow,
+    tasks: TasksWindow,
+    editor: EventEditor,
+    week_scroll: WeekScroll
+}
+impl MainScreen {
+    // Mouse wheel and horizontal swipes over the table switch weeks
+    fn handle_scroll(&mut self, app: &mut MainApp, ctx: &egui::Context) {
+        let (scroll_delta, time) = {
+            let input = ctx.input();
+            (input.scroll_delta, input.time)
+        };
+        let shift = self.week_scroll.update(scroll_delta.x + scroll_delta.y, time);
+        if shift != 0 {
+            app.shift_shown_week(shift);
+        }
+    }
+}
+impl Screen for MainScreen {
+    fn show(&mut self, app: &mut MainApp, ctx: &egui::Context) {
+        use egui::*;
+
+        if app.is_triggered(ctx, Action::Search) {
+            self.search.open = true;
+        }
+        let strings = app.strings();
+        show_view_tabs(app, ctx, View::Week, |app, ui| {
+            if ui.button(Action::Search.label(strings)).clicked() {
+                self.search.open = true;
+            }
+            if ui.button(strings.free_time).clicked() {
+                self.free_time.open = true;
+            }
+            if ui.button(strings.tasks).clicked() {
+                self.tasks.open = true;
+            }
+            if ui.button(strings.new_event).clicked() {
+                let day = if same_week(app.current_week(), app.shown_week) { app.current_day() } else { week_monday(app.shown_week) };
+                self.editor.open_new(day);
+            }
+        });
+        show_week_navigation(app, ctx);
+        self.search.show(app, ctx);
+        self.free_time.show(app, ctx);
+        self.tasks.show(app, ctx);
+        self.editor.show(app, ctx);
+
+        egui::CentralPanel::default()
+            .frame(Frame::none())
+            .show(ctx, |ui| {
+                if app.is_triggered(ctx, Action::NextWeek) {
+                    app.shift_shown_week(1);
+                }
+                if app.is_triggered(ctx, Action::PreviousWeek) {
+                    app.shift_shown_week(-1);
+                }
+                if app.is_triggered(ctx, Action::CurrentWeek) {
+                    app.set_shown_week(app.current_week());
+                }
+
+                let mut events_table = EventsTable::new(&app.shown_events);
+                events_table.week = Some(app.shown_week);
+                events_table.now = Some(vilnius_now());
+                events_table.language = app.language();
+                events_table.tasks = &app.user_data.tasks;
+                events_table.module_colours = app.profile().map(|profile| &profile.module_colours);
+                events_table.break_texture = Some(app.assets.as_ref().unwrap().break_texture.clone());
+                let (response, clicked_event) = events_table.show(ui);
+                if let Some(event) = clicked_event.cloned() {
+                    self.editor.open_event(app, &event);
+                }
+                if response.hovered() {
+                    self.handle_scroll(app, ctx);
+                }
+        });
+    }
+}
+
+struct DayScreen {
+    day: NaiveDate,
+    editor: EventEditor
+}
+impl DayScreen {
+    fn new(day: NaiveDate) -> Self {
+        Self { day, editor: EventEditor::default() }
+    }
+
+    fn set_day(&mut self, app: &mut MainApp, day: NaiveDate) {
+        if app.can_show_week(day.iso_week()) {
+            self.day = day;
+            app.set_shown_week(day.iso_week());
+        }
+    }
+
+    fn shift_day(&mut self, app: &mut MainApp, shift: i64) {
+        let mut day = self.day + Duration::days(shift);
+        while is_weekend(day) && !app.has_events_on(day) {
+            day = day + Duration::days(shift.signum());
+        }
+        self.set_day(app, day);
+    }
+}
+impl Screen for DayScreen {
+    fn show(&mut self, app: &mut MainApp, ctx: &egui::Context) {
+        use egui::*;
+
+        let strings = app.strings();
+        show_view_tabs(app, ctx, View::Day, |app, ui| {
+            ui.separator();
+            if ui.button("◀").clicked() {
+                self.shift_day(app, -1);
+            }
+            if ui.button(strings.today).clicked() {
+                let today = app.current_day();
+                self.set_day(app, today);
+            }
+            if ui.button("▶").clicked() {
+                self.shift_day(app, 1);
+            }
+            ui.separator();
+            if ui.button(strings.new_event).clicked() {
+                self.editor.open_new(self.day);
+            }
+        });
+        self.editor.show(app, ctx);
+
+        egui::CentralPanel::default()
+            .frame(Frame::none())
+            .show(ctx, |ui| {
+                if app.is_triggered(ctx, Action::NextWeek) {
+                    self.shift_day(app, 1);
+                }
+                if app.is_triggered(ctx, Action::PreviousWeek) {
+                    self.shift_day(app, -1);
+                }
+                if app.is_triggered(ctx, Action::CurrentWeek) {
+                    let today = app.current_day();
+                    self.set_day(app, today);
+                }
+
+                let events: Vec<ktu_timetable::timetable::Event> = app.shown_events.iter()
+                    .filter(|e| e.overlaps_date(self.day))
+                    .map(|e| e.clone())
+                    .collect();
+                let mut events_table = EventsTable::new(&events);
+                events_table.week = Some(app.shown_week);
+                events_table.day = Some(self.day);
+                events_table.now = Some(vilnius_now());
+                events_table.language = app.language();
+                events_table.tasks = &app.user_data.tasks;
+                events_table.module_colours = app.profile().map(|profile| &profile.module_colours);
+                events_table.break_texture = Some(app.assets.as_ref().unwrap().break_texture.clone());
+                if let (_, Some(event)) = events_table.show(ui) {
+                    self.editor.open_event(app, event);
+                }
+        });
+    }
+}
+
+#[derive(Default)]
+struct AgendaScreen {}
+impl Screen for AgendaScreen {
+    fn show(&mut self, app: &mut MainApp, ctx: &egui::Context) {
+        use egui::*;
+
+        let strings = app.strings();
+        show_view_tabs(app, ctx, View::Agenda, |_, _| {});
+
+        let now = vilnius_now();
+        let upcoming: Vec<ktu_timetable::timetable::Event> = match &app.timetable {
+            Some(timetable) => timetable.events.iter()
+                .filter(|e| e.end > now)
+                .filter(|e| app.is_event_visible(e))
+                .map(|e| e.clone())
+                .collect(),
+            None => vec![]
+        };
+
+        egui::CentralPanel::default()
+            .show(ctx, |ui| {
+                if upcoming.is_empty() {
+                    ui.label(strings.no_upcoming_events);
+                    return;
+                }
+
+                let text_size = egui::TextStyle::Body.resolve(ui.style()).size;
+                ScrollArea::vertical().show(ui, |ui| {
+                    let mut current_date = None;
+                    for event in &upcoming {
+                        let date = event.start.date();
+                        if current_date != Some(date) {
+                            current_date = Some(date);
+                            ui.add_space(text_size*0.5);
+                            let day_name = strings.full_day_names[date.weekday().num_days_from_monday() as usize];
+                            ui.heading(format!("{}, {}", day_name, date.format(strings.date_format)));
+                            ui.separator();
+                        }
+
+                        let response = ui.horizontal(|ui| {
+                            let (marker, _) = ui.allocate_exact_size(vec2(text_size*0.5, text_size), Sense::hover());
+                            ui.painter().rect_filled(marker, Rounding::from(2.0), get_event_bg(event, app.profile().map(|profile| &profile.module_colours)));
+                            ui.monospace(event.time_label());
+                            ui.label(event.module_label());
+                            ui.weak(event.location.as_str());
+                        }).response.interact(Sense::click());
+
+                        if response.clicked() {
+                            app.set_shown_week(event.start.iso_week());
+                            app.switch_to_main();
+                        }
+                    }
+                });
+        });
+    }
+}
+
+// Per module attendance over the semester so far
+#[derive(Default)]
+struct AttendanceWindow {
+    open: bool
+}
+
+impl AttendanceWindow {
+    fn show(&mut self, app: &mut MainApp, ctx: &egui::Context) {
+        let strings = app.strings();
+        let stats = match &app.timetable {
+            Some(timetable) => {
+                let events = timetable.events.iter().filter(|e| app.is_event_visible(e));
+                app.user_data.attendance_stats(events, vilnius_now())
+            },
+            None => vec![]
+        };
+
+        egui::Window::new(strings.attendance)
+            .open(&mut self.open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                if stats.is_empty() {
+                    ui.label(strings.nothing_found);
+                    return;
+                }
+
+                egui::Grid::new("attendance").striped(true).show(ui, |ui| {
+                    ui.strong(strings.module);
+                    for attendance in Attendance::ALL {
+                        ui.strong(attendance.label(strings));
+                    }
+                    ui.strong(strings.not_marked);
+                    ui.strong("%");
+                    ui.end_row();
+
+                    for module_stats in &stats {
+                        ui.label(module_stats.module.as_str());
+                        ui.label(module_stats.attended.to_string());
+                        ui.label(module_stats.missed.to_string());
+                        ui.label(module_stats.excused.to_string());
+                        ui.label(module_stats.unmarked.to_string());
+                        match module_stats.rate() {
+                            Some(rate) => ui.label(format!("{:.0}%", rate * 100.0)),
+                            None => ui.label("-")
+                        };
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+}
+
+#[derive(Default)]
+struct OverviewScreen {
+    attendance: AttendanceWindow
+}
+impl Screen for OverviewScreen {
+    fn show(&mut self, app: &mut MainApp, ctx: &egui::Context) {
+        let strings = app.strings();
+        show_view_tabs(app, ctx, View::Overview, |_, ui| {
+            if ui.button(strings.attendance).clicked() {
+                self.attendance.open = true;
+            }
+        });
+        self.attendance.show(app, ctx);
+
+        egui::CentralPanel::default()
+            .show(ctx, |ui| {
+                let clicked_week = match &app.timetable {
+                    Some(timetable) => {
+                        let mut overview = SemesterOverview::new(&timetable.events);
+                        overview.highlighted_week = Some(app.shown_week);
+                        overview.today = Some(vilnius_now().date());
+                        overview.language = app.language();
+                        overview.show(ui)
+                    },
+                    None => None
+                };
+
+                if let Some(week) = clicked_week {
+                    app.set_shown_week(week);
+                    app.switch_to_main();
+                }
+        });
+    }
+}
+
+const WEEK_BAR_HEIGHT: f32 = 18.0;
+
+#[derive(Default)]
+struct StatisticsScreen {}
+impl StatisticsScreen {
+    fn show_weekly_load(&self, ui: &mut egui::Ui, stats: &Statistics, strings: &Strings) {
+        use egui::*;
+
+        let max_minutes = stats.max_week_minutes().max(1);
+        Grid::new("weekly-load").striped(true).show(ui, |ui| {
+            for week in &stats.weeks {
+                ui.monospace(format!("{:02} {}", week.week.week(), strings.week_short));
+
+                let (rect, _) = ui.allocate_exact_size(vec2(200.0, WEEK_BAR_HEIGHT), Sense::hover());
+                let bar = Rect::from_min_size(rect.min, vec2(rect.width() * week.minutes as f32 / max_minutes as f32, rect.height()));
+                ui.painter().rect_filled(rect, Rounding::from(3.0), ui.visuals().widgets.noninteractive.bg_fill);
+                ui.painter().rect_filled(bar, Rounding::from(3.0), ui.visuals().selection.bg_fill);
+
+                ui.label(duration_label(week.minutes, strings));
+                let time = |time: Option<NaiveTime>| time.map_or("-".to_string(), |time| time.format("%H:%M").to_string());
+                ui.weak(format!("{} - {}", time(week.earliest), time(week.latest)));
+                ui.end_row();
+            }
+        });
+    }
+}
+impl Screen for StatisticsScreen {
+    fn show(&mut self, app: &mut MainApp, ctx: &egui::Context) {
+        use egui::*;
+
+        let strings = app.strings();
+        show_view_tabs(app, ctx, View::Statistics, |_, _| {});
+
+        let stats = match &app.timetable {
+            Some(timetable) => Statistics::new(timetable.events.iter().filter(|e| app.is_event_visible(e)), vilnius_now()),
+            None => Statistics::default()
+        };
+
+        egui::CentralPanel::default()
+            .show(ctx, |ui| {
+                let text_size = egui::TextStyle::Body.resolve(ui.style()).size;
+                ScrollArea::vertical().show(ui, |ui| {
+                    ui.heading(strings.contact_hours);
+                    Grid::new("contact-hours").show(ui, |ui| {
+                        ui.label(strings.total);
+                        ui.label(duration_label(stats.total_minutes, strings));
+                        ui.end_row();
+                        ui.label(strings.remaining);
+                        ui.label(duration_label(stats.remaining_minutes, strings));
+                        ui.end_row();
+                    });
+
+                    ui.add_space(text_size);
+                    ui.heading(strings.module);
+                    Grid::new("module-hours").striped(true).show(ui, |ui| {
+                        for (module, minutes) in &stats.per_module {
+                            ui.label(module.as_str());
+                            ui.label(duration_label(*minutes, strings));
+                            ui.end_row();
+                        }
+                    });
+
+                    ui.add_space(text_size);
+                    ui.heading(strings.kind);
+                    Grid::new("kind-hours").striped(true).show(ui, |ui| {
+                        for (kind, minutes) in &stats.per_kind {
+                            let (marker, _) = ui.allocate_exact_size(vec2(text_size*2.0, text_size), Sense::hover());
+                            ui.painter().rect_filled(marker, Rounding::from(2.0), get_category_bg(*kind));
+                            ui.label(duration_label(*minutes, strings));
+                            ui.end_row();
+                        }
+                    });
+
+                    ui.add_space(text_size);
+                    ui.heading(strings.busiest_days);
+                    Grid::new("weekday-hours").striped(true).show(ui, |ui| {
+                        for (day, minutes) in stats.per_weekday.iter().enumerate() {
+                            if *minutes > 0 {
+                                ui.label(strings.full_day_names[day]);
+                                ui.label(duration_label(*minutes, strings));
+                                ui.end_row();
+                            }
+                        }
+                    });
+                    ui.add_space(text_size*0.5);
+                    for (date, minutes) in &stats.busiest_dates {
+                        ui.label(format!("{}  {}", date.format(strings.date_format), duration_label(*minutes, strings)));
+                    }
+
+                    ui.add_space(text_size);
+                    ui.heading(strings.weekly_load);
+                    self.show_weekly_load(ui, &stats, strings);
+                });
+        });
+    }
+}
+
+// Window with an error, which stays open until it is closed
+fn show_error_window(ctx: &egui::Context, title: &str, hint: &str, error: &mut Option<String>) {
+    let mut open = error.is_some();
+    if let Some(message) = error.as_deref() {
+        egui::Window::new(title)
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.colored_label(egui::Color32::RED, message);
+                ui.label(hint);
+            });
+    }
+    if !open {
+        *error = None;
+    }
+}
+
+#[derive(Default)]
+struct VidkoScreen {
+    vidko_textfield: String,
+    get_error: Option<GetTimetableError>,
+    pending: Option<PendingTimetable>,
+    // Fetched timetable and its vidko code, waiting to be confirmed
+    preview: Option<(String, Timetable)>,
+    // Profile which was active before switching to a profile without a code, Cancel goes back to it
+    previous_profile: Option<String>
+}
+impl VidkoScreen {
+    // The previous profile could have been deleted meanwhile
+    fn previous_profile(&self, app: &MainApp) -> Option<String> {
+        let profile_names = app.config.as_ref().map_or(vec![], Config::profile_names);
+        self.previous_profile.clone().filter(|previous| profile_names.contains(previous))
+    }
+
+    // Only on the first run there is nothing to go back to
+    fn can_cancel(&self, app: &MainApp) -> bool {
+        self.previous_profile(app).is_some() || app.vidko().is_some()
+    }
 
-    timetable_getter: Box<dyn TimetableGetter>,
-    pub timetable: Option<Timetable>,
+    fn cancel(&mut self, app: &mut MainApp, ctx: &egui::Context) {
+        match self.previous_profile(app) {
+            Some(previous) => app.switch_profile(&previous, ctx),
+            None => {
+                // The code is kept, its timetable is downloaded again if it failed before
+                if app.timetable.is_none() {
+                    app.refresh_timetable(ctx);
+                }
+                app.switch_to_main();
+            }
+        }
+    }
 
-    config_store: Box<dyn ConfigStore>,
-    config: Option<Config>,
+    fn show_input(&mut self, app: &mut MainApp, ui: &mut egui::Ui) {
+        use egui::*;
 
-    assets: Option<AppAssets>,
+        let strings = app.strings();
+        ui.label(strings.enter_vidko);
+        ui.horizontal(|ui| {
+            ui.label(format!("{}: ", strings.vidko));
+            if ui.text_edit_singleline(&mut self.vidko_textfield).changed() {
+                self.get_error = None;
+            }
+        });
 
-    screen: Option<Rc<RefCell<dyn Screen>>>
-}
+        let vidko = normalize_vidko(&self.vidko_textfield);
+        let is_valid = is_valid_vidko(&vidko);
+        if !vidko.is_empty() && !is_valid {
+            ui.colored_label(Color32::RED, strings.vidko_format_hint);
+        }
 
-#[inline]
-fn is_weekend(time: NaiveDate) -> bool {
-    let day = time.weekday();
-    return day == Weekday::Sat || day == Weekday::Sun;
-}
+        ui.horizontal(|ui| {
+            if self.can_cancel(app) && ui.button(strings.cancel).clicked() {
+                self.cancel(app, ui.ctx());
+            }
+            if ui.add_enabled(is_valid && self.pending.is_none(), Button::new(strings.submit)).clicked() {
+                self.pending = Some(PendingTimetable::start(&app.timetable_getter, vec![vidko.clone()], ui.ctx()));
+            }
+            if self.pending.is_some() {
+                ui.spinner();
+            }
+        });
 
-fn get_current_week() -> IsoWeek {
-    let now = Local::now();
-    if is_weekend(now.date_naive()) {
-        now.add(Duration::days(7)).iso_week()
-    } else {
-        now.iso_week()
+        match &self.get_error {
+            Some(GetTimetableError::NotFound | GetTimetableError::EmptyTimetable) => {
+                ui.colored_label(Color32::RED, strings.invalid_vidko);
+            },
+            Some(e) => {
+                ui.colored_label(Color32::RED, e.to_string());
+            },
+            None => {}
+        }
     }
-}
-
-fn get_future_week(week_offset: u64) -> IsoWeek {
-    let now_week = get_current_week();
-    let week_date = NaiveDate::from_isoywd_opt(now_week.year(), now_week.week(), Weekday::Mon).expect("Invalid week or year given");
-    week_date.checked_add_days(Days::new(7 * week_offset)).unwrap().iso_week()
-}
-
-trait Screen {
-    fn show(&mut self, app: &mut MainApp, ctx: &egui::Context);
-}
 
-#[derive(Default)]
-struct MainScreen {}
-impl Screen for MainScreen {
-    fn show(&mut self, app: &mut MainApp, ctx: &egui::Context) {
+    fn show_preview(&mut self, app: &mut MainApp, ui: &mut egui::Ui) {
         use egui::*;
-        egui::CentralPanel::default()
-            .frame(Frame::none())
-            .show(ctx, |ui| {
-                if ctx.input().key_pressed(Key::D) && app.shown_week < get_future_week(MAX_FUTURE_WEEKS) {
-                    app.shift_shown_week(1);
-                }
-                if ctx.input().key_pressed(Key::A) && get_current_week() < app.shown_week {
-                    app.shift_shown_week(-1);
-                }
-                if ctx.input().key_pressed(Key::S) {
-                    app.set_shown_week(get_current_week());
-                }
-                if ctx.input().key_pressed(Key::F2) {
-                    if ctx.style().visuals.dark_mode {
-                        ctx.set_visuals(egui::Visuals::light());
-                    } else {
-                        ctx.set_visuals(egui::Visuals::dark());
+
+        let strings = app.strings();
+        let mut back = false;
+        let mut confirm = false;
+        let mut cancel = false;
+        if let Some((vidko, timetable)) = &self.preview {
+            ui.heading(format!("{}: {}", strings.vidko, vidko));
+            ui.label(format!("{}: {}", strings.events, timetable.events.len()));
+            if let (Some(first), Some(last)) = (timetable.first_date(), timetable.last_date()) {
+                ui.label(format!("{}: {} – {}", strings.period, first.format(strings.date_format), last.format(strings.date_format)));
+            }
+            ui.label(format!("{}:", strings.modules));
+            ScrollArea::vertical()
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    for module in timetable.module_names() {
+                        ui.label(module);
                     }
+                });
+            ui.horizontal(|ui| {
+                back = ui.button(strings.back).clicked();
+                confirm = ui.button(strings.confirm).clicked();
+                if self.can_cancel(app) && ui.button(strings.cancel).clicked() {
+                    cancel = true;
                 }
+            });
+        }
 
-                let mut events_table = EventsTable::new(&app.shown_events);
-                events_table.week = Some(app.shown_week);
-                events_table.now = Some(Local::now().naive_local());
-                events_table.break_texture = Some(app.assets.as_ref().unwrap().break_texture.clone());
-                ui.add(events_table);
-        });
-    }
-}
+        if cancel {
+            self.cancel(app, ui.ctx());
+            return;
+        }
 
-#[derive(Default)]
-struct VidkoScreen {
-    vidko_textfield: String,
-    get_error: Option<GetTimetableError>
+        if back {
+            self.preview = None;
+        }
+        if confirm {
+            let (vidko, timetable) = self.preview.take().unwrap();
+            let profile = app.config.get_or_insert_with(Config::default).profile_mut();
+            profile.vidko = Some(vidko);
+            let has_extra_vidkos = !profile.extra_vidkos.is_empty();
+            app.set_timetable(timetable);
+            // The preview only has the main timetable
+            if has_extra_vidkos {
+                app.refresh_timetable(ui.ctx());
+            }
+            app.switch_to_main();
+        }
+    }
 }
 impl Screen for VidkoScreen {
     fn show(&mut self, app: &mut MainApp, ctx: &egui::Context) {
-        use egui::*;
+        if let Some(result) = self.pending.as_ref().and_then(PendingTimetable::poll) {
+            let vidko = self.pending.take().unwrap().vidkos.remove(0);
+            match result {
+                Ok(timetable) => {
+                    self.preview = Some((vidko, timetable));
+                    self.get_error = None;
+                },
+                Err(e) => {
+                    self.get_error = Some(e);
+                }
+            }
+        }
 
         egui::CentralPanel::default()
             .show(ctx, |ui| {
                 ui.vertical(|ui| {
-                    ui.label("Įveskite savo vidko kodą");
-                    ui.horizontal(|ui| {
-                        ui.label("Vidko: ");
-                        ui.text_edit_singleline(&mut self.vidko_textfield);
-                    });
-                    if ui.button("Įvesti").clicked() {
-                        match app.timetable_getter.get(&self.vidko_textfield) {
-                            Ok(timetable) => {
-                                if app.config.is_none() {
-                                    app.config = Some(Config::default());
-                                }
-                                app.config.as_mut().unwrap().vidko = Some(self.vidko_textfield.clone());
-                                app.set_timetable(timetable);
-                                app.switch_to_main();
-                            },
-                            Err(e) => {
-                                self.get_error = Some(e);
-                            },
-                        }
-                    }
-                    if self.get_error.is_some() {
-                        ui.colored_label(Color32::RED, "Netinkamas kodas");
+                    if self.preview.is_some() {
+                        self.show_preview(app, ui);
+                    } else {
+                        self.show_input(app, ui);
                     }
                 });
         });
@@ -133,15 +1466,38 @@ impl Screen for VidkoScreen {
 }
 
 impl MainApp {
-    pub fn new(config_store: Box<dyn ConfigStore>, timetable_getter: Box<dyn TimetableGetter>) -> Self {
+    pub fn new(config_store: Box<dyn ConfigStore>, user_data_store: Box<dyn UserDataStore>, timetable_getter: Box<dyn TimetableGetter>) -> Self {
         Self {
             timetable: None,
-            shown_week: get_current_week(),
+            refresh: None,
+            refresh_error: None,
+            cache_error: None,
+            applied_http: None,
+            http_error: None,
+            timetable_generation: 0,
+            shown_week: get_current_week(vilnius_now(), &WeekRollover::default()),
             shown_events: vec![],
             assets: None,
             config_store,
             config: None,
-            timetable_getter,
+            saved_config: None,
+            user_data_store,
+            user_data: UserData::default(),
+            saved_user_data: UserData::default(),
+            timetable_getter: Arc::from(timetable_getter),
+            settings_open: false,
+            shortcuts_open: false,
+            rebinding: None,
+            new_profile_name: String::new(),
+            deleting_profile: false,
+            new_extra_vidko: String::new(),
+            config_error: None,
+            config_warning: None,
+            user_data_error: None,
+            save_error: None,
+            config_unsaved: false,
+            user_data_unsaved: false,
+            close_blocked: false,
             screen: None
         }
     }
@@ -154,18 +1510,27 @@ impl MainApp {
         });
 
         self.config = match self.config_store.load() {
-            Ok(config) => Some(config),
-            Err(_) => None,
+            Ok(loaded) => {
+                self.config_warning = loaded.dropped_message();
+                Some(loaded.config)
+            },
+            Err(LoadConfigError::NotFound) => None,
+            Err(e) => {
+                self.config_error = Some(e.to_string());
+                None
+            }
         };
+        self.saved_config = self.config.clone();
+        self.load_user_data();
+        self.shown_week = self.current_week();
+        self.apply_http_config();
 
         if self.vidko().is_none() {
             self.switch_to_vidko();
         } else {
-            if self.refresh_timetable().is_err() {
-                self.switch_to_vidko();
-            } else {
-                self.switch_to_main();
-            }
+            // Goes to the vidko screen if the download fails and nothing was downloaded before, see `poll_refresh`
+            self.refresh_timetable(&cc.egui_ctx);
+            self.switch_to_main();
         }
     }
 
@@ -177,45 +1542,471 @@ impl MainApp {
         self.screen = Some(Rc::new(RefCell::new(VidkoScreen::default())));
     }
 
+    fn language(&self) -> Language {
+        match &self.config {
+            Some(config) => config.language,
+            None => Language::default()
+        }
+    }
+
+    fn strings(&self) -> &'static Strings {
+        self.language().strings()
+    }
+
+    fn week_rollover(&self) -> WeekRollover {
+        match &self.config {
+            Some(config) => config.week_rollover,
+            None => WeekRollover::default()
+        }
+    }
+
+    fn current_week(&self) -> IsoWeek {
+        get_current_week(vilnius_now(), &self.week_rollover())
+    }
+
+    fn current_day(&self) -> NaiveDate {
+        get_current_day(vilnius_now(), &self.week_rollover())
+    }
+
+    fn keybindings(&self) -> Keybindings {
+        match &self.config {
+            Some(config) => config.keybindings.clone(),
+            None => Keybindings::default()
+        }
+    }
+
+    // Shortcuts are ignored while typing into text fields or picking a new shortcut
+    fn is_triggered(&self, ctx: &egui::Context, action: Action) -> bool {
+        if ctx.wants_keyboard_input() || self.rebinding.is_some() {
+            return false;
+        }
+        is_triggered(&self.keybindings(), &ctx.input(), action)
+    }
+
+    fn toggle_theme(&self, ctx: &egui::Context) {
+        if ctx.style().visuals.dark_mode {
+            ctx.set_visuals(egui::Visuals::light());
+        } else {
+            ctx.set_visuals(egui::Visuals::dark());
+        }
+    }
+
+    fn capture_rebinding(&mut self, ctx: &egui::Context) {
+        let action = match self.rebinding {
+            Some(action) => action,
+            None => return
+        };
+
+        let events = ctx.input().events.clone();
+        for event in events {
+            if let egui::Event::Key { key, pressed: true, modifiers, .. } = event {
+                if key != egui::Key::Escape {
+                    let config = self.config.get_or_insert_with(Config::default);
+                    config.keybindings.set(action, Shortcut::new(key, modifiers).to_string());
+                }
+                self.rebinding = None;
+                return;
+            }
+        }
+    }
+
+    fn show_settings_window(&mut self, ctx: &egui::Context) {
+        let strings = self.strings();
+        let mut open = self.settings_open;
+        egui::Window::new(Action::OpenSettings.label(strings))
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                self.show_profiles(ui);
+
+                if let Some(vidko) = self.vidko() {
+                    let vidko = vidko.to_string();
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}: {}", strings.vidko, vidko));
+                        if ui.button(strings.change).clicked() {
+                            self.switch_to_vidko();
+                        }
+                    });
+                    self.show_extra_vidkos(ui);
+                }
+
+                let mut language = self.language();
+                egui::ComboBox::from_label(strings.language)
+                    .selected_text(language.name())
+                    .show_ui(ui, |ui| {
+                        for option in Language::ALL {
+                            ui.selectable_value(&mut language, option, option.name());
+                        }
+                    });
+                if language != self.language() {
+                    self.config.get_or_insert_with(Config::default).language = language;
+                }
+
+                let mut dark_mode = ctx.style().visuals.dark_mode;
+                if ui.checkbox(&mut dark_mode, strings.dark_theme).changed() {
+                    self.toggle_theme(ctx);
+                }
+
+                ui.separator();
+                let mut rollover = self.week_rollover();
+                ui.checkbox(&mut rollover.enabled, strings.week_rollover);
+                ui.add_enabled_ui(rollover.enabled, |ui| {
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source("rollover-weekday")
+                            .selected_text(strings.full_day_names[rollover.weekday.num_days_from_monday() as usize])
+                            .show_ui(ui, |ui| {
+                                let mut weekday = Weekday::Mon;
+                                for name in strings.full_day_names {
+                                    ui.selectable_value(&mut rollover.weekday, weekday, name);
+                                    weekday = weekday.succ();
+                                }
+                            });
+                        ui.add(egui::DragValue::new(&mut rollover.hour).clamp_range(0..=23).suffix(format!(" {}", strings.hours_short)));
+                    });
+                });
+                if rollover != self.week_rollover() {
+                    self.config.get_or_insert_with(Config::default).week_rollover = rollover;
+                }
+
+                ui.separator();
+                ui.label(Action::ShowShortcuts.label(strings));
+                let keybindings = self.keybindings();
+                egui::Grid::new("keybindings").striped(true).show(ui, |ui| {
+                    for action in Action::ALL {
+                        ui.label(action.label(strings));
+                        let text = if self.rebinding == Some(action) { "..." } else { keybindings.get(action) };
+                        if ui.button(text).clicked() {
+                            self.rebinding = Some(action);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if ui.button(strings.reset_defaults).clicked() {
+                    let config = self.config.get_or_insert_with(Config::default);
+                    config.keybindings = Keybindings::default();
+                }
+
+                ui.separator();
+                self.show_http_settings(ui);
+            });
+        self.settings_open = open;
+    }
+
+    // Only the settings people usually need to change, the rest are in the `[http]` section of config.toml
+    fn show_http_settings(&mut self, ui: &mut egui::Ui) {
+        let strings = self.strings();
+        let mut http = self.config.as_ref().map(|config| config.http.clone()).unwrap_or_default();
+        ui.label(strings.http_settings);
+        egui::Grid::new("http-settings").show(ui, |ui| {
+            ui.label(strings.timeout);
+            ui.add(egui::DragValue::new(&mut http.timeout_secs).clamp_range(1..=300).suffix(" s"));
+            ui.end_row();
+
+            ui.label(strings.retries);
+            ui.add(egui::DragValue::new(&mut http.retries).clamp_range(0..=10));
+            ui.end_row();
+
+            ui.label(strings.proxy);
+            let mut proxy = http.proxy.clone().unwrap_or_default();
+            ui.text_edit_singleline(&mut proxy);
+            let proxy = proxy.trim();
+            http.proxy = if proxy.is_empty() { None } else { Some(proxy.to_string()) };
+            ui.end_row();
+        });
+        if let Some(e) = &self.http_error {
+            ui.colored_label(egui::Color32::RED, e);
+        }
+
+        if self.config.as_ref().map_or(http != HttpConfig::default(), |config| config.http != http) {
+            self.config.get_or_insert_with(Config::default).http = http;
+        }
+    }
+
+    // Timetables of other groups which are shown together with the profile's own
+    fn show_extra_vidkos(&mut self, ui: &mut egui::Ui) {
+        let strings = self.strings();
+        let vidkos = self.vidkos();
+        ui.label(strings.extra_vidkos);
+
+        let mut removed = None;
+        for (i, vidko) in vidkos.iter().enumerate().skip(1) {
+            ui.horizontal(|ui| {
+                ui.label(vidko.as_str());
+                if ui.small_button("✖").clicked() {
+                    removed = Some(i - 1);
+                }
+            });
+        }
+
+        let mut added = None;
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_extra_vidko);
+            let vidko = normalize_vidko(&self.new_extra_vidko);
+            let can_add = is_valid_vidko(&vidko) && !vidkos.contains(&vidko);
+            if ui.add_enabled(can_add, egui::Button::new(strings.add)).clicked() {
+                added = Some(vidko);
+            }
+        });
+
+        if removed.is_none() && added.is_none() {
+            return;
+        }
+        let profile = self.config.get_or_insert_with(Config::default).profile_mut();
+        if let Some(i) = removed {
+            profile.extra_vidkos.remove(i);
+        }
+        if let Some(vidko) = added {
+            profile.extra_vidkos.push(vidko);
+            self.new_extra_vidko.clear();
+        }
+        self.refresh_timetable(ui.ctx());
+    }
+
+    fn show_profiles(&mut self, ui: &mut egui::Ui) {
+        let strings = self.strings();
+        let profile_names = self.config.as_ref().map_or(vec![], |config| config.profile_names());
+        let active = self.profile().map(|profile| profile.name.clone());
+
+        let mut selected = active.clone();
+        egui::ComboBox::from_label(strings.profile)
+            .selected_text(selected.as_deref().unwrap_or(""))
+            .show_ui(ui, |ui| {
+                for name in &profile_names {
+                    ui.selectable_value(&mut selected, Some(name.clone()), name.as_str());
+                }
+            });
+        if selected != active {
+            if let Some(name) = selected {
+                self.switch_profile(&name, ui.ctx());
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_profile_name);
+            let name = self.new_profile_name.trim().to_string();
+            if ui.add_enabled(!name.is_empty(), egui::Button::new(strings.add)).clicked() {
+                if self.config.get_or_insert_with(Config::default).add_profile(&name) {
+                    self.new_profile_name.clear();
+                    self.switch_profile(&name, ui.ctx());
+                }
+            }
+            if profile_names.len() > 1 && ui.button(strings.delete).clicked() {
+                self.deleting_profile = true;
+            }
+        });
+        if self.deleting_profile {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} \"{}\"?", strings.delete_profile, self.profile_name()));
+                if ui.button(strings.confirm).clicked() {
+                    self.delete_active_profile(ui.ctx());
+                }
+                if ui.button(strings.cancel).clicked() {
+                    self.deleting_profile = false;
+                }
+            });
+        }
+        ui.separator();
+    }
+
+    // Switches to another profile first, so the deleted one's changes are still saved into its own file.
+    // That file is kept, adding a profile with the same name brings the data back.
+    fn delete_active_profile(&mut self, ctx: &egui::Context) {
+        let deleted = self.profile_name();
+        let next = self.config.as_ref()
+            .and_then(|config| config.profiles.iter().find(|profile| profile.name != deleted))
+            .map(|profile| profile.name.clone());
+        if let Some(next) = next {
+            self.switch_profile(&next, ctx);
+            if let Some(config) = &mut self.config {
+                config.remove_profile(&deleted);
+            }
+        }
+        self.deleting_profile = false;
+    }
+
+    // Saves the config and user data as soon as they change, so nothing is lost if the app crashes
+    fn save_changes(&mut self) {
+        if self.config != self.saved_config {
+            self.save_config();
+            // Failed saves are not retried on every frame, only after the next change or before closing
+            self.saved_config = self.config.clone();
+        }
+
+        if self.user_data != self.saved_user_data {
+            self.save_user_data();
+            self.saved_user_data = self.user_data.clone();
+        }
+    }
+
+    fn save_config(&mut self) {
+        if let Some(config) = &self.config {
+            let result = self.config_store.save(config);
+            self.config_unsaved = result.is_err();
+            if let Err(e) = result {
+                self.save_error = Some(e.to_string());
+            }
+        }
+    }
+
+    fn save_user_data(&mut self) {
+        let result = self.user_data_store.save(&self.profile_name(), &self.user_data);
+        self.user_data_unsaved = result.is_err();
+        if let Err(e) = result {
+            self.save_error = Some(e.to_string());
+        }
+    }
+
+    fn show_shortcuts_window(&mut self, ctx: &egui::Context) {
+        let strings = self.strings();
+        let keybindings = self.keybindings();
+        egui::Window::new(Action::ShowShortcuts.label(strings))
+            .open(&mut self.shortcuts_open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("shortcuts").striped(true).show(ui, |ui| {
+                    for action in Action::ALL {
+                        ui.label(action.label(strings));
+                        ui.monospace(keybindings.get(action));
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+
+    fn switch_to_view(&mut self, view: View) {
+        match view {
+            View::Week => self.switch_to_main(),
+            View::Day => {
+                let today = self.current_day();
+                let day = if same_week(today.iso_week(), self.shown_week) { today } else { week_monday(self.shown_week) };
+                self.screen = Some(Rc::new(RefCell::new(DayScreen::new(day))));
+            },
+            View::Agenda => {
+                self.screen = Some(Rc::new(RefCell::new(AgendaScreen::default())));
+            },
+            View::Overview => {
+                self.screen = Some(Rc::new(RefCell::new(OverviewScreen::default())));
+            },
+            View::Statistics => {
+                self.screen = Some(Rc::new(RefCell::new(StatisticsScreen::default())));
+            }
+        }
+    }
+
+    #[inline]
+    pub fn profile(&self) -> Option<&Profile> {
+        self.config.as_ref().and_then(Config::profile)
+    }
+
+    fn vidkos(&self) -> Vec<String> {
+        self.profile().map_or(vec![], Profile::vidkos)
+    }
+
+    fn profile_name(&self) -> String {
+        self.profile().map_or(DEFAULT_PROFILE.to_string(), |profile| profile.name.clone())
+    }
+
     #[inline]
     pub fn vidko(&self) -> Option<&str> {
-        if let Some(config) = &self.config {
-            return config.vidko.as_deref();
+        if let Some(profile) = self.profile() {
+            return profile.vidko.as_deref();
         }
         None
     }
 
-    pub fn refresh_timetable(&mut self) -> Result<(), GetTimetableError> {
-        let vidko;
-        {
-            if self.vidko().is_none() {
-                self.shown_events = vec![];
-                self.timetable = None;
-                return Err(GetTimetableError::NotFound);
+    // User data of the active profile
+    fn load_user_data(&mut self) {
+        self.user_data = match self.user_data_store.load(&self.profile_name()) {
+            Ok(user_data) => user_data,
+            Err(LoadConfigError::NotFound) => UserData::default(),
+            Err(e) => {
+                self.user_data_error = Some(e.to_string());
+                UserData::default()
             }
-            vidko = self.vidko().unwrap();
+        };
+        self.saved_user_data = self.user_data.clone();
+    }
+
+    fn switch_profile(&mut self, name: &str, ctx: &egui::Context) {
+        self.deleting_profile = false;
+        let previous = self.profile_name();
+        // Changes are saved to the profile they were made in
+        self.save_changes();
+        self.config.get_or_insert_with(Config::default).active_profile = name.to_string();
+        self.load_user_data();
+        // The previous profile's timetable isn't shown while the new one downloads
+        self.shown_events = vec![];
+        self.timetable = None;
+        self.timetable_generation += 1;
+        if self.vidko().is_none() {
+            self.refresh = None;
+            self.screen = Some(Rc::new(RefCell::new(VidkoScreen { previous_profile: Some(previous), ..VidkoScreen::default() })));
+        } else {
+            self.refresh_timetable(ctx);
+            self.switch_to_main();
+        }
+    }
+
+    // Starts downloading the active profile's timetables, a download already in progress is dropped
+    pub fn refresh_timetable(&mut self, ctx: &egui::Context) {
+        let vidkos = self.vidkos();
+        if !vidkos.is_empty() {
+            self.refresh = Some(PendingTimetable::start(&self.timetable_getter, vidkos, ctx));
         }
+    }
 
-        let timetable = match self.timetable_getter.get(vidko) {
-            Ok(timetable) => timetable,
-            Err(e) => return Err(e),
+    fn poll_refresh(&mut self) {
+        let result = match self.refresh.as_ref().and_then(PendingTimetable::poll) {
+            Some(result) => result,
+            None => return
         };
+        if let Some(warning) = self.timetable_getter.take_warning() {
+            self.cache_error = Some(warning);
+        }
+        // Different codes could have been entered while downloading
+        if self.refresh.take().unwrap().vidkos != self.vidkos() {
+            return;
+        }
 
-        self.shown_events = timetable.by_week(self.shown_week);
-        self.timetable = Some(timetable);
+        match result {
+            Ok(timetable) => {
+                self.refresh_error = None;
+                self.set_timetable(timetable);
+            },
+            Err(e) => {
+                self.refresh_error = Some(e.to_string());
+                // A failed refresh keeps showing the old timetable. Without one the last downloaded one is shown,
+                // only when there is none the code is asked again.
+                if self.timetable.is_none() {
+                    match self.timetable_getter.cached_merged(&self.vidkos()) {
+                        Some(timetable) => self.set_timetable(timetable),
+                        None => self.switch_to_vidko()
+                    }
+                }
+            }
+        }
+    }
+
+    // Gives changed HTTP settings to the getter, an invalid proxy or certificate keeps the previous ones
+    fn apply_http_config(&mut self) {
+        let http = self.config.as_ref().map(|config| config.http.clone()).unwrap_or_default();
+        if self.applied_http.as_ref() == Some(&http) {
+            return;
+        }
 
-        Ok(())
+        self.http_error = self.timetable_getter.set_http_config(&http).err().map(|e| e.to_string());
+        self.applied_http = Some(http);
     }
 
     pub fn set_timetable(&mut self, timetable: Timetable) {
-        self.shown_events = timetable.by_week(self.shown_week);
         self.timetable = Some(timetable);
+        self.timetable_generation += 1;
+        self.update_shown_events();
     }
 
-    fn shift_shown_week(&mut self, shift: i32) {
-        let year = self.shown_week.year();
-        let week = self.shown_week.week();
-        let week_date = NaiveDate::from_isoywd_opt(year, week, Weekday::Mon).expect("Invalid week or year given");
+    fn shifted_week(&self, shift: i32) -> IsoWeek {
+        let week_date = week_monday(self.shown_week);
         let shifted_week;
         if shift > 0 {
             shifted_week = week_date.checked_add_days(Days::new(7 * (shift as u64)));
@@ -223,28 +2014,127 @@ impl MainApp {
             shifted_week = week_date.checked_sub_days(Days::new(7 * ((-shift) as u64)));
         }
 
-        if let Some(shifted_week) = shifted_week {
-            self.set_shown_week(shifted_week.iso_week());
+        shifted_week.map_or(self.shown_week, |date| date.iso_week())
+    }
+
+    fn shift_shown_week(&mut self, shift: i32) {
+        let shifted_week = self.shifted_week(shift);
+        if self.can_show_week(shifted_week) {
+            self.set_shown_week(shifted_week);
+        }
+    }
+
+    // Weeks from the first to the last event of the timetable, always including the current week
+    fn week_range(&self) -> (IsoWeek, IsoWeek) {
+        let current_week = self.current_week();
+        match &self.timetable {
+            Some(timetable) => {
+                let first_week = timetable.first_date().map_or(current_week, |date| date.iso_week());
+                let last_week = timetable.last_date().map_or(current_week, |date| date.iso_week());
+                (first_week.min(current_week), last_week.max(current_week))
+            },
+            None => (current_week, current_week)
         }
     }
 
+    fn can_show_week(&self, week: IsoWeek) -> bool {
+        let (first_week, last_week) = self.week_range();
+        let monday = week_monday(week);
+        week_monday(first_week) <= monday && monday <= week_monday(last_week)
+    }
+
     fn set_shown_week(&mut self, week: IsoWeek) {
         self.shown_week = week;
+        self.update_shown_events();
+    }
 
+    // Official events of the shown week, together with the user's own events and notes
+    fn update_shown_events(&mut self) {
+        let monday = week_monday(self.shown_week).and_hms_opt(0, 0, 0).unwrap();
+        let mut events = self.user_data.events_between(monday, monday + Duration::days(7));
         if let Some(timetable) = &self.timetable {
-            self.shown_events = timetable.by_week(self.shown_week);
+            events.extend(
+                timetable.by_week(self.shown_week)
+                    .into_iter()
+                    .filter(|e| self.is_event_visible(e))
+            );
+        }
+        self.user_data.annotate_events(&mut events);
+        events.sort_by_key(|e| e.start);
+        self.shown_events = events;
+    }
+
+    // Module code of the module with this label, if the timetable has one
+    fn module_code(&self, module: &str) -> Option<String> {
+        self.timetable.as_ref()?
+            .events.iter()
+            .find(|e| e.module_label() == module)
+            .and_then(|e| e.module_code.clone())
+    }
+
+    fn has_events_on(&self, date: NaiveDate) -> bool {
+        match &self.timetable {
+            Some(timetable) => timetable.events.iter().any(|e| e.overlaps_date(date)),
+            None => false
+        }
+    }
+
+    fn is_event_visible(&self, event: &Event) -> bool {
+        match self.profile() {
+            Some(profile) => !profile.hidden_modules.iter().any(|module| module == event.module_label()),
+            None => true
+        }
+    }
+
+    fn set_module_colour(&mut self, module: &str, colour: Option<EventCategory>) {
+        let profile = self.config.get_or_insert_with(Config::default).profile_mut();
+        match colour {
+            Some(colour) => profile.module_colours.insert(module.to_string(), colour),
+            None => profile.module_colours.remove(module)
+        };
+    }
+
+    fn set_module_hidden(&mut self, module: &str, hidden: bool) {
+        let profile = self.config.get_or_insert_with(Config::default).profile_mut();
+        profile.hidden_modules.retain(|m| m != module);
+        if hidden {
+            profile.hidden_modules.push(module.to_string());
         }
+        self.update_shown_events();
     }
 }
 
 impl eframe::App for MainApp {
-    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
-        if let Some(config) = &self.config {
-            self.config_store.save(config).unwrap();
+    // Changes are saved as they happen, this only retries saves which failed. Unchanged user data
+    // isn't saved, so data which couldn't be read isn't replaced by an empty one.
+    fn on_close_event(&mut self) -> bool {
+        if self.close_blocked {
+            return true;
         }
+        if self.config_unsaved {
+            self.save_config();
+        }
+        if self.user_data_unsaved {
+            self.save_user_data();
+        }
+        // The window stays open once more, so that the error can still be seen
+        self.close_blocked = self.config_unsaved || self.user_data_unsaved;
+        !self.close_blocked
     }
 
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if self.is_triggered(ctx, Action::ToggleTheme) {
+            self.toggle_theme(ctx);
+        }
+        if self.is_triggered(ctx, Action::Refresh) {
+            self.refresh_timetable(ctx);
+        }
+        if self.is_triggered(ctx, Action::OpenSettings) {
+            self.settings_open = true;
+        }
+        if self.is_triggered(ctx, Action::ShowShortcuts) {
+            self.shortcuts_open = !self.shortcuts_open;
+        }
 
         if let Some(screen) = self.screen.clone() {
             screen.borrow_mut().show(self, ctx)
@@ -252,5 +2142,22 @@ impl eframe::App for MainApp {
             // TODO: show error screen
             todo!()
         }
+
+        self.show_settings_window(ctx);
+        self.show_shortcuts_window(ctx);
+        let strings = self.strings();
+        show_error_window(ctx, strings.config_error, strings.config_reset, &mut self.config_error);
+        show_error_window(ctx, strings.config_partly_read, strings.config_left_out, &mut self.config_warning);
+        show_error_window(ctx, strings.user_data_error, strings.user_data_reset, &mut self.user_data_error);
+        let save_hint = if self.close_blocked { strings.close_without_saving } else { strings.changes_not_saved };
+        show_error_window(ctx, strings.save_failed, save_hint, &mut self.save_error);
+        let refresh_hint = if self.timetable.is_some() { strings.showing_last_download } else { strings.check_vidko };
+        show_error_window(ctx, strings.refresh_failed, refresh_hint, &mut self.refresh_error);
+        show_error_window(ctx, strings.save_failed, strings.not_available_offline, &mut self.cache_error);
+
+        self.save_changes();
+        self.apply_http_config();
+        self.poll_refresh();
+        self.capture_rebinding(ctx);
     }
 }
@@ -1,14 +1,18 @@
-use crate::{timetable::{TimetableGetter, BlockingTimetableGetter}, config::{ConfigStore, TomlConfigStore}};
+use crate::{timetable::{TimetableGetter, BlockingTimetableGetter}, config::{ConfigStore, TomlConfigStore}, user_data::{UserDataStore, TomlUserDataStore}};
 
+/// The timetable source and stores the app and server mode run with,
+/// the default one downloads timetables and keeps files in `config::data_dir`
 pub struct Environment {
     pub timetable_getter: Box<dyn TimetableGetter>,
-    pub config_store: Box<dyn ConfigStore>
+    pub config_store: Box<dyn ConfigStore>,
+    pub user_data_store: Box<dyn UserDataStore>
 }
 
 impl Default for Environment {
     fn default() -> Self {
         Self {
             config_store: Box::new(TomlConfigStore::default()),
+            user_data_store: Box::new(TomlUserDataStore::default()),
             timetable_getter: Box::new(BlockingTimetableGetter::default())
         }
     }
@@ -1,16 +1,23 @@
-use std::ops::Add;
+use std::collections::BTreeMap;
 
 use eframe::{egui, epaint::text::TextWrapping};
-use chrono::{Datelike, Timelike, NaiveDate, Weekday, IsoWeek, Duration, NaiveDateTime, Local};
-use egui::{Color32, TextureHandle, Rect, text::LayoutJob, Visuals, Stroke, Widget};
-use crate::timetable::{Event, EventCategory};
+use chrono::{Datelike, Timelike, NaiveDate, IsoWeek, Duration, NaiveDateTime};
+use egui::{Color32, TextureHandle, Rect, text::LayoutJob, Visuals, Stroke};
+use ktu_timetable::{timetable::{Event, EventCategory}, user_data::{Task, Attendance}, utils::{week_monday, same_week}, i18n::Language, timezone::vilnius_now};
 
 pub struct EventsTable<'a> {
     pub break_texture: Option<TextureHandle>,
 
     pub week: Option<IsoWeek>,
+    // When set, only this day is shown with larger, more detailed cards
+    pub day: Option<NaiveDate>,
     pub now: Option<NaiveDateTime>,
-    pub events: &'a [Event]
+    pub language: Language,
+    pub events: &'a [Event],
+    // Deadlines are marked in the columns of their days
+    pub tasks: &'a [Task],
+    // `Profile::module_colours` of the active profile
+    pub module_colours: Option<&'a BTreeMap<String, EventCategory>>
 }
 
 fn count_minutes(time: &str) -> u32 {
@@ -18,16 +25,26 @@ fn count_minutes(time: &str) -> u32 {
     return 60*time_h.parse::<u32>().unwrap() + time_m.parse::<u32>().unwrap();
 }
 
-fn get_category_bg(category: EventCategory) -> Color32 {
+pub fn get_category_bg(category: EventCategory) -> Color32 {
     match category {
         EventCategory::Default => Color32::GRAY,
         EventCategory::Yellow => Color32::from_rgb(251, 184, 41),
         EventCategory::Green => Color32::from_rgb(152, 188, 55),
         EventCategory::Red => Color32::from_rgb(247, 83, 65),
         EventCategory::Blue => Color32::from_rgb(10, 174, 179),
+        EventCategory::Personal => Color32::from_rgb(176, 140, 230),
     }
 }
 
+// Official events can be given another colour from the same palette, personal ones keep theirs
+pub fn get_event_bg(event: &Event, module_colours: Option<&BTreeMap<String, EventCategory>>) -> Color32 {
+    let picked = match module_colours {
+        Some(colours) if event.personal_id.is_none() => colours.get(event.module_label()).copied(),
+        _ => None
+    };
+    get_category_bg(picked.unwrap_or(event.category))
+}
+
 fn draw_repeating_texture(ui: &mut egui::Ui, texture: Option<&TextureHandle>, rect: Rect, tint: Color32) {
     use egui::*;
     let painter = ui.painter();
@@ -88,12 +105,6 @@ fn draw_repeating_texture(ui: &mut egui::Ui, texture: Option<&TextureHandle>, re
     }
 }
 
-#[inline]
-fn is_weekend(time: NaiveDate) -> bool {
-    let day = time.weekday();
-    return day == Weekday::Sat || day == Weekday::Sun;
-}
-
 #[inline]
 fn shift_color(color: Color32, amount: f32) -> Color32 {
     return Color32::from_rgb(
@@ -104,14 +115,21 @@ fn shift_color(color: Color32, amount: f32) -> Color32 {
 }
 
 const HEADER_SIZE: f32 = 50.0;
+// Shorter events, like reminders without an end, still get a card which can be clicked on
+const MIN_CARD_MINUTES: i64 = 30;
+const TASK_COLOR: Color32 = Color32::from_rgb(200, 30, 60);
 
 impl<'a> EventsTable<'a> {
     pub fn new(events: &'a [Event]) -> Self {
         Self {
             break_texture: None,
             week: None,
+            day: None,
             now: None,
-            events
+            language: Language::default(),
+            events,
+            tasks: &[],
+            module_colours: None
         }
     }
 
@@ -135,7 +153,8 @@ impl<'a> EventsTable<'a> {
         shift_color(self.bg_fill(visuals), 0.5)
     }
 
-    fn show_event(&self, ui: &mut egui::Ui, event: &Event, mut rect: Rect) {
+    // Returns true if the card was clicked on
+    fn show_event(&self, ui: &mut egui::Ui, event: &Event, column: usize, mut rect: Rect) -> bool {
         use egui::*;
         let margin = 6.0;
         let border_size = 4.0;
@@ -145,11 +164,15 @@ impl<'a> EventsTable<'a> {
         rect.set_width(rect.width().max(text_size*6.0));
 
         let painter = ui.painter();
-        let color = get_category_bg(event.category);
+        let color = get_event_bg(event, self.module_colours);
         let rounding = Rounding::from(5.0);
-        let border_color = color.linear_multiply(1.25);
         painter.rect_filled(rect, rounding, color);
-        painter.rect_stroke(rect.shrink(border_size/2.0), rounding, (border_size, border_color));
+        if event.personal_id.is_some() {
+            // Personal events are outlined, so they stand out from the official ones
+            painter.rect_stroke(rect.shrink(border_size/2.0), rounding, (border_size, text_color.linear_multiply(0.6)));
+        } else {
+            painter.rect_stroke(rect.shrink(border_size/2.0), rounding, (border_size, color.linear_multiply(1.25)));
+        }
 
         ui.allocate_ui_at_rect(rect.shrink(margin), |ui| {
             let font = FontId::proportional(text_size * 0.8);
@@ -167,25 +190,75 @@ impl<'a> EventsTable<'a> {
             ui.label(job);
 
             ui.add_space(text_size*0.2);
-            let time_label = format!("{} - {}", event.start_time.format("%H:%M"), event.end_time.format("%H:%M"));
-            ui.label(RichText::new(time_label).color(text_color).font(font));
+            ui.label(RichText::new(event.time_label()).color(text_color).font(font.clone()));
+
+            if let Some(note) = &event.note {
+                let mut job = LayoutJob::single_section(format!("📝 {}", note), TextFormat {
+                    color: text_color,
+                    font_id: font.clone(),
+                    italics: true,
+                    ..TextFormat::default()
+                });
+                job.wrap = TextWrapping {
+                    max_rows: if self.day.is_some() { 3 } else { 1 },
+                    ..Default::default()
+                };
+                ui.label(job);
+            }
+
+            if self.day.is_some() {
+                ui.label(RichText::new(&event.location).color(text_color).font(font.clone()));
+                let mut job = LayoutJob::single_section(event.description.clone(), TextFormat {
+                    color: text_color,
+                    font_id: font,
+                    ..TextFormat::default()
+                });
+                job.wrap = TextWrapping {
+                    max_rows: 3,
+                    ..Default::default()
+                };
+                ui.label(job);
+            }
         });
+
+        if let Some(attendance) = event.attendance {
+            let symbol = match attendance {
+                Attendance::Attended => "✔",
+                Attendance::Missed => "✖",
+                Attendance::Excused => "⊘"
+            };
+            ui.painter().text(
+                rect.right_top() + vec2(-margin, margin),
+                Align2::RIGHT_TOP,
+                symbol,
+                FontId::proportional(text_size),
+                text_color
+            );
+        }
+
+        let id = ui.id().with(("event", event.key(), column));
+        ui.interact(rect, id, Sense::click())
+            .on_hover_cursor(CursorIcon::PointingHand)
+            .clicked()
     }
 
-    fn show_header(&self, ui: &mut egui::Ui, rect: Rect, week: IsoWeek) {
+    fn show_header(&self, ui: &mut egui::Ui, rect: Rect, days: &[NaiveDate]) {
         use egui::*;
         let painter = ui.painter();
         let visuals = ui.visuals();
         painter.rect_filled(rect, Rounding::none(), self.dark_bg_fill(visuals));
 
-        let column_width = rect.width()/5.0;
+        let column_width = rect.width()/days.len() as f32;
         let header_size = rect.height();
         let text_size = egui::TextStyle::Body.resolve(ui.style()).size;
         let text_color = self.fg_stroke(visuals).color;
 
         // Draw day names
-        for (i, name) in ["Pir", "Ant", "Tre", "Ket", "Pen"].iter().enumerate() {
+        let strings = self.language.strings();
+        let day_names = if days.len() == 1 { strings.full_day_names } else { strings.day_names };
+        for (i, day) in days.iter().enumerate() {
             let offset = column_width * (i as f32 + 0.5);
+            let name = day_names[day.weekday().num_days_from_monday() as usize];
 
             painter.text(
                 rect.left_top() + vec2(offset, header_size/2.5),
@@ -197,34 +270,54 @@ impl<'a> EventsTable<'a> {
         }
 
         // Draw dates
-        let year = week.year();
-        let week = week.week();
-        let mut week_date = NaiveDate::from_isoywd_opt(year, week, Weekday::Mon).expect("Invalid week or year given");
-        for i in 1..=5 {
-            let offset = column_width * i as f32;
+        for (i, day) in days.iter().enumerate() {
+            let offset = column_width * (i+1) as f32;
 
             painter.text(
                 rect.left_top() + vec2(offset-3.0, header_size-3.0),
                 Align2::RIGHT_BOTTOM,
-                week_date.format("%m-%d").to_string(),
+                day.format(strings.short_date_format).to_string(),
                 FontId::proportional(text_size*0.85),
                 text_color
             );
-            week_date = week_date.add(Duration::days(1));
         }
     }
 
+    // Minutes of the day at which each card starts and ends on `day`, None if the event doesn't happen on it
+    fn card_minutes(event: &Event, day: NaiveDate) -> Option<(i64, i64)> {
+        let (start, end) = event.clipped_to(day)?;
+        let day_start = day.and_hms_opt(0, 0, 0).unwrap();
+        let start_minute = (start - day_start).num_minutes().min(24*60 - MIN_CARD_MINUTES);
+        let end_minute = (end - day_start).num_minutes().max(start_minute + MIN_CARD_MINUTES);
+        Some((start_minute, end_minute))
+    }
+
+    // The usual lecture hours, extended to whole hours to fit earlier and later events of the shown days
+    fn shown_minutes(&self, days: &[NaiveDate], usual_first: i64, usual_last: i64) -> (i64, i64) {
+        let mut first_minute = usual_first;
+        let mut last_minute = usual_last;
+        for event in self.events {
+            for day in days {
+                if let Some((start_minute, end_minute)) = Self::card_minutes(event, *day) {
+                    first_minute = first_minute.min(start_minute / 60 * 60);
+                    last_minute = last_minute.max((end_minute + 59) / 60 * 60);
+                }
+            }
+        }
+        (first_minute, last_minute)
+    }
+
     fn show_body(
         &self,
         ui: &mut egui::Ui,
         rect: Rect,
-        week: IsoWeek,
+        days: &[NaiveDate],
         now: NaiveDateTime
-    ) {
+    ) -> Option<&'a Event> {
         use egui::*;
 
         let painter = ui.painter();
-        let column_width = rect.width()/5.0;
+        let column_width = rect.width()/days.len() as f32;
         let column_gap = 3.0;
 
         let visuals = ui.visuals();
@@ -234,8 +327,12 @@ impl<'a> EventsTable<'a> {
 
         let timestamps = ["9:00", "10:30", "11:00", "12:30", "13:30", "15:00", "15:30", "17:00"];
         let timestamps_mins = timestamps.map(count_minutes);
-        let total_minutes = timestamps_mins.last().unwrap() - timestamps_mins.first().unwrap();
-        let minute_to_pixel_scale = rect.height()/total_minutes as f32;
+        let (first_minute, last_minute) = self.shown_minutes(
+            days,
+            *timestamps_mins.first().unwrap() as i64,
+            *timestamps_mins.last().unwrap() as i64
+        );
+        let minute_to_pixel_scale = rect.height()/(last_minute - first_minute) as f32;
 
         // draw bg
         painter.rect_filled(
@@ -245,17 +342,18 @@ impl<'a> EventsTable<'a> {
         );
 
         // Highlight current day column
-        if now.iso_week() == week && !is_weekend(now.date()) {
-            let days_from_monday = now.weekday().num_days_from_monday() as f32;
+        let today_column = days.iter().position(|day| *day == now.date());
+        if let Some(column) = today_column {
+            let column = column as f32;
             let rect = Rect::from_min_max(
-                rect.left_top() + vec2(column_width * days_from_monday, 0.0),
-                rect.left_bottom() + vec2(column_width * (1.0+days_from_monday), 0.0)
+                rect.left_top() + vec2(column_width * column, 0.0),
+                rect.left_bottom() + vec2(column_width * (1.0+column), 0.0)
             );
             painter.rect_filled(rect, Rounding::none(), highlight_color);
         }
 
         // Draw gaps between columns
-        for i in 1..5 {
+        for i in 1..days.len() {
             let offset = column_width * i as f32;
             painter.line_segment([
                 rect.left_top() + vec2(offset, 0.0),
@@ -263,10 +361,18 @@ impl<'a> EventsTable<'a> {
             ], (column_gap, dark_bg_fill))
         }
 
-        // Mark break times
-        for i in (1..timestamps_mins.len()-1).step_by(2) {
-            let from = (timestamps_mins[i]   - timestamps_mins[0]) as f32 * minute_to_pixel_scale;
-            let to   = (timestamps_mins[i+1] - timestamps_mins[0]) as f32 * minute_to_pixel_scale;
+        // Mark break times, and the hours before and after lectures when they are shown
+        let mut breaks: Vec<(i64, i64)> = (1..timestamps_mins.len()-1).step_by(2)
+            .map(|i| (timestamps_mins[i] as i64, timestamps_mins[i+1] as i64))
+            .collect();
+        breaks.push((first_minute, timestamps_mins[0] as i64));
+        breaks.push((*timestamps_mins.last().unwrap() as i64, last_minute));
+        for (from, to) in breaks {
+            if to <= from {
+                continue;
+            }
+            let from = (from - first_minute) as f32 * minute_to_pixel_scale;
+            let to   = (to   - first_minute) as f32 * minute_to_pixel_scale;
             draw_repeating_texture(
                 ui,
                 self.break_texture.as_ref(),
@@ -278,23 +384,58 @@ impl<'a> EventsTable<'a> {
             );
         }
 
-        // Draw event cards
+        // Draw event cards, events spanning multiple days get a card in each column
+        let mut clicked_event = None;
         for event in self.events {
-            let day = event.date.weekday().num_days_from_monday() as usize;
-            let duration = (event.end_time - event.start_time).num_minutes() as f32;
-            let start_time = event.start_time.hour()*60 + event.start_time.minute() - timestamps_mins[0];
-            let event_rect = Rect::from_min_size(
-                rect.left_top() + vec2(column_width*day as f32, start_time as f32*minute_to_pixel_scale),
-                vec2(column_width, duration*minute_to_pixel_scale)
-            ).shrink2(vec2(10.0, 0.0));
-            self.show_event(ui, event, event_rect);
+            for (column, day) in days.iter().enumerate() {
+                let (start_minute, end_minute) = match Self::card_minutes(event, *day) {
+                    Some(minutes) => minutes,
+                    None => continue
+                };
+
+                let event_rect = Rect::from_min_size(
+                    rect.left_top() + vec2(column_width*column as f32, (start_minute - first_minute) as f32*minute_to_pixel_scale),
+                    vec2(column_width, (end_minute - start_minute) as f32*minute_to_pixel_scale)
+                ).shrink2(vec2(10.0, 0.0));
+                if self.show_event(ui, event, column, event_rect) {
+                    clicked_event = Some(event);
+                }
+            }
+        }
+
+        // Task deadlines, ones outside of the shown hours are pinned to the top or bottom
+        let text_size = egui::TextStyle::Body.resolve(ui.style()).size;
+        for task in self.tasks {
+            let column = match days.iter().position(|day| *day == task.due.date()) {
+                Some(column) => column,
+                None => continue
+            };
+            let minute = (task.due.hour() * 60 + task.due.minute()) as i64;
+            let offset = (minute.clamp(first_minute, last_minute) - first_minute) as f32 * minute_to_pixel_scale;
+            let column_rect = Rect::from_min_size(
+                rect.left_top() + vec2(column_width * column as f32, 0.0),
+                vec2(column_width, rect.height())
+            );
+            let painter = ui.painter().with_clip_rect(column_rect);
+            let color = if task.done { dark_bg_fill } else { TASK_COLOR };
+            let left = column_rect.left_top() + vec2(0.0, offset);
+            painter.line_segment([left, left + vec2(column_width, 0.0)], (2.0, color));
+
+            let align = if offset < text_size { Align2::LEFT_TOP } else { Align2::LEFT_BOTTOM };
+            painter.text(
+                left + vec2(4.0, 0.0),
+                align,
+                format!("⚑ {}: {}", task.module, task.title),
+                FontId::proportional(text_size*0.75),
+                color
+            );
         }
 
         // now line
         let painter = ui.painter();
-        let current_time = now.minute() + now.hour() * 60;
-        if current_time > timestamps_mins[0] && current_time < *timestamps_mins.last().unwrap_or(&0) && !is_weekend(now.date()) {
-            let offset = (current_time - timestamps_mins[0]) as f32 * minute_to_pixel_scale;
+        let current_time = (now.minute() + now.hour() * 60) as i64;
+        if current_time > first_minute && current_time < last_minute && today_column.is_some() {
+            let offset = (current_time - first_minute) as f32 * minute_to_pixel_scale;
             let points = [
                 rect.left_top()  + vec2(0.0, offset),
                 rect.right_top() + vec2(0.0, offset)
@@ -304,18 +445,34 @@ impl<'a> EventsTable<'a> {
             painter.line_segment(points, (thickness + 2.0 * border_size, dark_bg_fill));
             painter.line_segment(points, (thickness, highlight_color));
         }
+
+        clicked_event
     }
-}
 
-impl<'a> Widget for EventsTable<'a> {
-    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+    // Returns the response of the whole table and the event which was clicked on
+    pub fn show(self, ui: &mut egui::Ui) -> (egui::Response, Option<&'a Event>) {
         use egui::*;
 
         let response = ui.allocate_rect(ui.min_rect(), Sense::hover());
         let rect = response.rect;
 
-        let now  = self.now.unwrap_or(Local::now().naive_local());
+        let now  = self.now.unwrap_or(vilnius_now());
         let week = self.week.unwrap_or(now.iso_week());
+        let days = match self.day {
+            Some(day) => vec![day],
+            None => {
+                // Weekends are only shown if there is something happening on them
+                let monday = week_monday(week);
+                let last_day = self.events.iter()
+                    .flat_map(|e| e.dates())
+                    .filter(|date| same_week(date.iso_week(), week))
+                    .map(|date| date.weekday().num_days_from_monday() as i64)
+                    .max()
+                    .unwrap_or(0)
+                    .max(4);
+                (0..=last_day).map(|i| monday + Duration::days(i)).collect()
+            }
+        };
 
         self.show_header(
             ui,
@@ -323,19 +480,19 @@ impl<'a> Widget for EventsTable<'a> {
                 rect.left_top(),
                 vec2(rect.width(), HEADER_SIZE)
             ),
-            week
+            &days
         );
 
-        self.show_body(
+        let clicked_event = self.show_body(
             ui,
             Rect::from_min_max(
                 rect.left_top() + vec2(0.0, HEADER_SIZE),
                 rect.right_bottom()
             ),
-            week,
+            &days,
             now
         );
 
-        response
+        (response, clicked_event)
     }
 }
\ No newline at end of file
@@ -0,0 +1,354 @@
+use serde::{Deserialize, Serialize};
+
+/// Language of the interface
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Language {
+    #[default]
+    Lithuanian,
+    English
+}
+
+impl Language {
+    /// In the order they are offered in the settings
+    pub const ALL: [Language; 2] = [Language::Lithuanian, Language::English];
+
+    /// Name of the language in that language
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::Lithuanian => "Lietuvių",
+            Language::English => "English",
+        }
+    }
+
+    /// Every text of the interface in this language
+    pub fn strings(&self) -> &'static Strings {
+        match self {
+            Language::Lithuanian => &LITHUANIAN,
+            Language::English => &ENGLISH,
+        }
+    }
+}
+
+/// Texts of the interface, there is one for every `Language`
+pub struct Strings {
+    /// Indexed by the number of days from monday
+    pub day_names: [&'static str; 7],
+    pub full_day_names: [&'static str; 7],
+    /// chrono format strings
+    pub short_date_format: &'static str,
+    pub date_format: &'static str,
+    pub week_short: &'static str,
+    pub hours_short: &'static str,
+    pub minutes_short: &'static str,
+
+    /// Indexed in the same order as `Action::ALL`
+    pub action_labels: [&'static str; 8],
+    /// Indexed in the same order as the `SearchField` variants
+    pub search_fields: [&'static str; 6],
+
+    pub week_view: &'static str,
+    pub day_view: &'static str,
+    pub agenda_view: &'static str,
+    pub overview_view: &'static str,
+    pub statistics_view: &'static str,
+    pub filters: &'static str,
+    pub today: &'static str,
+
+    pub enter_vidko: &'static str,
+    pub vidko: &'static str,
+    pub submit: &'static str,
+    pub invalid_vidko: &'static str,
+    pub vidko_format_hint: &'static str,
+    pub events: &'static str,
+    pub period: &'static str,
+    pub modules: &'static str,
+    pub back: &'static str,
+    pub confirm: &'static str,
+    pub cancel: &'static str,
+    pub profile: &'static str,
+    pub delete_profile: &'static str,
+    pub extra_vidkos: &'static str,
+    pub config_error: &'static str,
+    pub config_reset: &'static str,
+    pub config_partly_read: &'static str,
+    pub config_left_out: &'static str,
+    pub user_data_error: &'static str,
+    pub user_data_reset: &'static str,
+    pub save_failed: &'static str,
+    pub changes_not_saved: &'static str,
+    pub close_without_saving: &'static str,
+    pub refresh_failed: &'static str,
+    pub showing_last_download: &'static str,
+    pub check_vidko: &'static str,
+    pub not_available_offline: &'static str,
+
+    pub nothing_found: &'static str,
+
+    pub free_time: &'static str,
+    pub add: &'static str,
+    pub mine: &'static str,
+    pub from: &'static str,
+    pub to: &'static str,
+    pub at_least: &'static str,
+    pub room: &'static str,
+    pub no_free_time: &'static str,
+
+    pub no_upcoming_events: &'static str,
+
+    pub change: &'static str,
+    pub dark_theme: &'static str,
+    pub week_rollover: &'static str,
+    pub reset_defaults: &'static str,
+    pub language: &'static str,
+    pub http_settings: &'static str,
+    pub timeout: &'static str,
+    pub retries: &'static str,
+    pub proxy: &'static str,
+
+    pub new_event: &'static str,
+    pub edit_event: &'static str,
+    pub title: &'static str,
+    pub location: &'static str,
+    pub description: &'static str,
+    pub note: &'static str,
+    pub save: &'static str,
+    pub delete: &'static str,
+    pub invalid_event_time: &'static str,
+
+    pub tasks: &'static str,
+    pub no_tasks: &'static str,
+    pub due: &'static str,
+    pub export_ics: &'static str,
+    pub exported_to: &'static str,
+
+    pub attendance: &'static str,
+    /// Indexed in the same order as `Attendance::ALL`
+    pub attendance_states: [&'static str; 3],
+    pub not_marked: &'static str,
+    pub module: &'static str,
+
+    pub contact_hours: &'static str,
+    pub total: &'static str,
+    pub remaining: &'static str,
+    pub kind: &'static str,
+    pub busiest_days: &'static str,
+    pub weekly_load: &'static str
+}
+
+static LITHUANIAN: Strings = Strings {
+    day_names: ["Pir", "Ant", "Tre", "Ket", "Pen", "Šeš", "Sek"],
+    full_day_names: ["Pirmadienis", "Antradienis", "Trečiadienis", "Ketvirtadienis", "Penktadienis", "Šeštadienis", "Sekmadienis"],
+    short_date_format: "%m-%d",
+    date_format: "%Y-%m-%d",
+    week_short: "sav.",
+    hours_short: "val.",
+    minutes_short: "min.",
+
+    action_labels: [
+        "Kita savaitė",
+        "Ankstesnė savaitė",
+        "Dabartinė savaitė",
+        "Perjungti temą",
+        "Atnaujinti tvarkaraštį",
+        "Nustatymai",
+        "Paieška",
+        "Spartieji klavišai"
+    ],
+    search_fields: ["Visur", "Modulis", "Modulio kodas", "Auditorija", "Dėstytojas", "Aprašymas"],
+
+    week_view: "Savaitė",
+    day_view: "Diena",
+    agenda_view: "Darbotvarkė",
+    overview_view: "Semestras",
+    statistics_view: "Statistika",
+    filters: "Filtrai",
+    today: "Šiandien",
+
+    enter_vidko: "Įveskite savo vidko kodą",
+    vidko: "Vidko",
+    submit: "Įvesti",
+    invalid_vidko: "Netinkamas kodas",
+    vidko_format_hint: "Kodą sudaro iki 16 raidžių ir skaičių, pvz. E1810",
+    events: "Užsiėmimai",
+    period: "Laikotarpis",
+    modules: "Moduliai",
+    back: "Atgal",
+    confirm: "Patvirtinti",
+    cancel: "Atšaukti",
+    profile: "Profilis",
+    delete_profile: "Ištrinti profilį",
+    extra_vidkos: "Kartu rodomi kiti tvarkaraščiai",
+    config_error: "Nepavyko nuskaityti nustatymų",
+    config_reset: "Naudojami numatytieji nustatymai.",
+    config_partly_read: "Dalies nustatymų nepavyko nuskaityti",
+    config_left_out: "Vietoj jų naudojami numatytieji.",
+    user_data_error: "Nepavyko nuskaityti jūsų įvykių, užduočių ir pastabų",
+    user_data_reset: "Pradedama be jų.",
+    save_failed: "Nepavyko išsaugoti",
+    changes_not_saved: "Paskutiniai pakeitimai neišsaugoti, jie bus išsaugoti kitą kartą ką nors pakeitus.",
+    close_without_saving: "Paskutiniai pakeitimai neišsaugoti. Uždarius langą dar kartą, jie bus prarasti.",
+    refresh_failed: "Nepavyko atsisiųsti tvarkaraščio",
+    showing_last_download: "Rodomas paskutinį kartą atsisiųstas tvarkaraštis.",
+    check_vidko: "Patikrinkite vidko kodą ir ryšį.",
+    not_available_offline: "Be interneto ryšio tvarkaraštis nebus rodomas.",
+
+    nothing_found: "Nieko nerasta",
+
+    free_time: "Laisvas laikas",
+    add: "Pridėti",
+    mine: "mano",
+    from: "Nuo",
+    to: "iki",
+    at_least: "ne trumpiau nei",
+    room: "Auditorija",
+    no_free_time: "Laisvo laiko šią savaitę nėra",
+
+    no_upcoming_events: "Artimiausių užsiėmimų nėra",
+
+    change: "Keisti",
+    dark_theme: "Tamsi tema",
+    week_rollover: "Rodyti kitą savaitę nuo",
+    reset_defaults: "Atkurti numatytuosius",
+    language: "Kalba",
+    http_settings: "Tvarkaraščio atsisiuntimas",
+    timeout: "Laiko limitas",
+    retries: "Bandymų kartoti",
+    proxy: "Tarpinis serveris",
+
+    new_event: "Naujas įvykis",
+    edit_event: "Redaguoti įvykį",
+    title: "Pavadinimas",
+    location: "Vieta",
+    description: "Aprašymas",
+    note: "Pastaba",
+    save: "Išsaugoti",
+    delete: "Ištrinti",
+    invalid_event_time: "Netinkamas laikas, naudokite formatą 2023-02-01 09:00",
+
+    tasks: "Užduotys",
+    no_tasks: "Užduočių nėra",
+    due: "Terminas",
+    export_ics: "Eksportuoti į ICS",
+    exported_to: "Išsaugota į",
+
+    attendance: "Lankomumas",
+    attendance_states: ["Dalyvavau", "Praleidau", "Pateisinta"],
+    not_marked: "Nepažymėta",
+    module: "Modulis",
+
+    contact_hours: "Kontaktinės valandos",
+    total: "Iš viso",
+    remaining: "Liko šį semestrą",
+    kind: "Užsiėmimo tipas",
+    busiest_days: "Užimčiausios dienos",
+    weekly_load: "Savaitės krūvis"
+};
+
+static ENGLISH: Strings = Strings {
+    day_names: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+    full_day_names: ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"],
+    short_date_format: "%d %b",
+    date_format: "%d %B %Y",
+    week_short: "wk.",
+    hours_short: "h",
+    minutes_short: "min",
+
+    action_labels: [
+        "Next week",
+        "Previous week",
+        "Current week",
+        "Toggle theme",
+        "Refresh timetable",
+        "Settings",
+        "Search",
+        "Keyboard shortcuts"
+    ],
+    search_fields: ["Everywhere", "Module", "Module code", "Room", "Lecturer", "Description"],
+
+    week_view: "Week",
+    day_view: "Day",
+    agenda_view: "Agenda",
+    overview_view: "Semester",
+    statistics_view: "Statistics",
+    filters: "Filters",
+    today: "Today",
+
+    enter_vidko: "Enter your vidko code",
+    vidko: "Vidko",
+    submit: "Submit",
+    invalid_vidko: "Invalid code",
+    vidko_format_hint: "The code is up to 16 letters and digits, e.g. E1810",
+    events: "Events",
+    period: "Period",
+    modules: "Modules",
+    back: "Back",
+    confirm: "Confirm",
+    cancel: "Cancel",
+    profile: "Profile",
+    delete_profile: "Delete profile",
+    extra_vidkos: "Other timetables shown together",
+    config_error: "Failed to read settings",
+    config_reset: "Default settings are used instead.",
+    config_partly_read: "Some settings couldn't be read",
+    config_left_out: "Their defaults are used instead.",
+    user_data_error: "Failed to read your events, tasks and notes",
+    user_data_reset: "Starting without them.",
+    save_failed: "Failed to save",
+    changes_not_saved: "The latest changes are not saved, saving is tried again after the next change.",
+    close_without_saving: "The latest changes are not saved. Closing the window again loses them.",
+    refresh_failed: "Failed to download the timetable",
+    showing_last_download: "The last downloaded timetable is shown.",
+    check_vidko: "Check the vidko code and the connection.",
+    not_available_offline: "The timetable won't be shown without a connection.",
+
+    nothing_found: "Nothing found",
+
+    free_time: "Free time",
+    add: "Add",
+    mine: "mine",
+    from: "From",
+    to: "to",
+    at_least: "at least",
+    room: "Room",
+    no_free_time: "No free time this week",
+
+    no_upcoming_events: "No upcoming classes",
+
+    change: "Change",
+    dark_theme: "Dark theme",
+    week_rollover: "Show the next week from",
+    reset_defaults: "Reset to defaults",
+    language: "Language",
+    http_settings: "Timetable downloads",
+    timeout: "Timeout",
+    retries: "Retries",
+    proxy: "Proxy",
+
+    new_event: "New event",
+    edit_event: "Edit event",
+    title: "Title",
+    location: "Location",
+    description: "Description",
+    note: "Note",
+    save: "Save",
+    delete: "Delete",
+    invalid_event_time: "Invalid time, use the format 2023-02-01 09:00",
+
+    tasks: "Tasks",
+    no_tasks: "No tasks",
+    due: "Due",
+    export_ics: "Export to ICS",
+    exported_to: "Saved to",
+
+    attendance: "Attendance",
+    attendance_states: ["Attended", "Missed", "Excused"],
+    not_marked: "Not marked",
+    module: "Module",
+
+    contact_hours: "Contact hours",
+    total: "Total",
+    remaining: "Remaining this semester",
+    kind: "Class type",
+    busiest_days: "Busiest days",
+    weekly_load: "Weekly load"
+};
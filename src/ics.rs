@@ -0,0 +1,266 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use crate::{timetable::Event, timezone::vilnius_to_utc, user_data::Task};
+
+const PRODUCT_ID: &str = "-//KTU Timetable//EN";
+// Lines longer than this need to be folded, as required by RFC 5545
+const MAX_LINE_LENGTH: usize = 75;
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+// Times are written in UTC, so calendar clients don't need to know about Europe/Vilnius
+fn format_datetime(datetime: NaiveDateTime) -> String {
+    vilnius_to_utc(datetime).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+struct IcsWriter {
+    output: String
+}
+
+impl IcsWriter {
+    fn new() -> Self {
+        let mut writer = Self { output: String::new() };
+        writer.line("BEGIN", "VCALENDAR");
+        writer.line("VERSION", "2.0");
+        writer.line("PRODID", PRODUCT_ID);
+        writer
+    }
+
+    // Writes "NAME:value", folding it into several lines when it's too long
+    fn line(&mut self, name: &str, value: &str) {
+        let line = format!("{}:{}", name, value);
+        let mut length = 0;
+        for c in line.chars() {
+            if length + c.len_utf8() > MAX_LINE_LENGTH {
+                self.output.push_str("\r\n ");
+                length = 1;
+            }
+            self.output.push(c);
+            length += c.len_utf8();
+        }
+        self.output.push_str("\r\n");
+    }
+
+    fn text(&mut self, name: &str, value: &str) {
+        self.line(name, &escape_text(value));
+    }
+
+    fn finish(mut self) -> String {
+        self.line("END", "VCALENDAR");
+        self.output
+    }
+}
+
+/// Calendar with a VTODO for every task
+pub fn tasks_to_ics(tasks: &[Task]) -> String {
+    tasks_to_ics_at(tasks, Utc::now())
+}
+
+fn tasks_to_ics_at(tasks: &[Task], now: DateTime<Utc>) -> String {
+    let now = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let mut writer = IcsWriter::new();
+    for task in tasks {
+        writer.line("BEGIN", "VTODO");
+        writer.line("UID", &format!("task-{}@ktu-timetable", task.id));
+        writer.line("DTSTAMP", &now);
+        writer.line("DUE", &format_datetime(task.due));
+        writer.text("SUMMARY", &format!("{}: {}", task.module, task.title));
+        writer.text("CATEGORIES", &task.module);
+        if task.done {
+            writer.line("STATUS", "COMPLETED");
+        } else {
+            writer.line("STATUS", "NEEDS-ACTION");
+        }
+        writer.line("END", "VTODO");
+    }
+    writer.finish()
+}
+
+fn event_uid(event: &Event) -> String {
+    match event.personal_id {
+        Some(id) => format!("event-{}@ktu-timetable", id),
+        None => format!("{}@ktu-timetable", event.key())
+    }
+}
+
+/// Calendar with a VEVENT for every event, which calendar clients can subscribe to
+pub fn events_to_ics(events: &[Event]) -> String {
+    events_to_ics_at(events, Utc::now())
+}
+
+fn events_to_ics_at(events: &[Event], now: DateTime<Utc>) -> String {
+    let now = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let mut writer = IcsWriter::new();
+    writer.text("X-WR-CALNAME", "KTU");
+    for event in events {
+        writer.line("BEGIN", "VEVENT");
+        writer.text("UID", &event_uid(event));
+        writer.line("DTSTAMP", &now);
+        writer.line("DTSTART", &format_datetime(event.start));
+        writer.line("DTEND", &format_datetime(event.end));
+        writer.text("SUMMARY", &event.summary);
+        writer.text("LOCATION", &event.location);
+        if let Some(note) = &event.note {
+            writer.text("DESCRIPTION", &format!("{}\n\n{}", event.description, note));
+        } else {
+            writer.text("DESCRIPTION", &event.description);
+        }
+        if let Some(module_code) = &event.module_code {
+            writer.text("CATEGORIES", module_code);
+        }
+        writer.line("END", "VEVENT");
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use crate::{test_utils::at, timetable::EventCategory};
+
+    use super::*;
+
+    fn stamp() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 2, 1, 12, 0, 0).unwrap()
+    }
+
+    fn event(location: &str, note: Option<&str>, personal_id: Option<u64>) -> Event {
+        let start = at(2023, 2, 6, 8, 30);
+        Event {
+            category: EventCategory::Yellow,
+            start,
+            end: start + Duration::minutes(90),
+            description: "Lecture".to_string(),
+            summary: "P175B123 Databases".to_string(),
+            location: location.to_string(),
+            module_name: Some("Databases".to_string()),
+            module_code: Some("P175B123".to_string()),
+            note: note.map(String::from),
+            attendance: None,
+            personal_id
+        }
+    }
+
+    fn written_line(name: &str, value: &str) -> String {
+        let mut writer = IcsWriter { output: String::new() };
+        writer.line(name, value);
+        writer.output
+    }
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(escape_text("a\\b;c,d\r\ne\nf"), "a\\\\b\\;c\\,d\\ne\\nf");
+        assert_eq!(escape_text("Plain text: ok"), "Plain text: ok");
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        let output = written_line("SUMMARY", &"x".repeat(100));
+        let lines: Vec<&str> = output.trim_end_matches("\r\n").split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), MAX_LINE_LENGTH);
+        assert_eq!(lines[1], format!(" {}", "x".repeat(100 + "SUMMARY:".len() - MAX_LINE_LENGTH)));
+
+        assert_eq!(written_line("SUMMARY", &"x".repeat(MAX_LINE_LENGTH - "SUMMARY:".len())).matches("\r\n").count(), 1);
+    }
+
+    #[test]
+    fn folds_between_characters() {
+        // "ą" takes two octets, so a line can't end in the middle of one
+        let value = "ą".repeat(100);
+        let output = written_line("SUMMARY", &value);
+        for line in output.trim_end_matches("\r\n").split("\r\n") {
+            assert!(line.len() <= MAX_LINE_LENGTH);
+        }
+        assert_eq!(output.lines().next().unwrap().len(), MAX_LINE_LENGTH - 1);
+        assert_eq!(output.replace("\r\n ", ""), format!("SUMMARY:{}\r\n", value));
+    }
+
+    #[test]
+    fn writes_tasks() {
+        let tasks = [
+            Task {
+                id: 4,
+                module: "Databases".to_string(),
+                module_code: Some("P175B123".to_string()),
+                title: "Report, part 1".to_string(),
+                due: at(2023, 3, 1, 12, 0),
+                done: false
+            },
+            Task {
+                id: 5,
+                module: "Databases".to_string(),
+                module_code: None,
+                title: "Quiz".to_string(),
+                due: at(2023, 4, 3, 9, 0),
+                done: true
+            }
+        ];
+        assert_eq!(tasks_to_ics_at(&tasks, stamp()), [
+            "BEGIN:VCALENDAR",
+            "VERSION:2.0",
+            "PRODID:-//KTU Timetable//EN",
+            "BEGIN:VTODO",
+            "UID:task-4@ktu-timetable",
+            "DTSTAMP:20230201T120000Z",
+            "DUE:20230301T100000Z",
+            "SUMMARY:Databases: Report\\, part 1",
+            "CATEGORIES:Databases",
+            "STATUS:NEEDS-ACTION",
+            "END:VTODO",
+            "BEGIN:VTODO",
+            "UID:task-5@ktu-timetable",
+            "DTSTAMP:20230201T120000Z",
+            "DUE:20230403T060000Z",
+            "SUMMARY:Databases: Quiz",
+            "CATEGORIES:Databases",
+            "STATUS:COMPLETED",
+            "END:VTODO",
+            "END:VCALENDAR",
+            ""
+        ].join("\r\n"));
+    }
+
+    #[test]
+    fn writes_events() {
+        let events = [
+            event("XI r.-101", Some("Bring a laptop"), None),
+            event("", None, Some(3))
+        ];
+        assert_eq!(events_to_ics_at(&events, stamp()), [
+            "BEGIN:VCALENDAR",
+            "VERSION:2.0",
+            "PRODID:-//KTU Timetable//EN",
+            "X-WR-CALNAME:KTU",
+            "BEGIN:VEVENT",
+            "UID:2023-02-06T08:30 P175B123 Databases XI r.-101@ktu-timetable",
+            "DTSTAMP:20230201T120000Z",
+            "DTSTART:20230206T063000Z",
+            "DTEND:20230206T080000Z",
+            "SUMMARY:P175B123 Databases",
+            "LOCATION:XI r.-101",
+            "DESCRIPTION:Lecture\\n\\nBring a laptop",
+            "CATEGORIES:P175B123",
+            "END:VEVENT",
+            "BEGIN:VEVENT",
+            "UID:event-3@ktu-timetable",
+            "DTSTAMP:20230201T120000Z",
+            "DTSTART:20230206T063000Z",
+            "DTEND:20230206T080000Z",
+            "SUMMARY:P175B123 Databases",
+            "LOCATION:",
+            "DESCRIPTION:Lecture",
+            "CATEGORIES:P175B123",
+            "END:VEVENT",
+            "END:VCALENDAR",
+            ""
+        ].join("\r\n"));
+    }
+}
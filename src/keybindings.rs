@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::Strings;
+
+/// Something in the app which can be done with a shortcut
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    NextWeek,
+    PreviousWeek,
+    CurrentWeek,
+    ToggleTheme,
+    Refresh,
+    OpenSettings,
+    Search,
+    ShowShortcuts
+}
+
+impl Action {
+    /// In the order they are listed in the settings
+    pub const ALL: [Action; 8] = [
+        Action::NextWeek,
+        Action::PreviousWeek,
+        Action::CurrentWeek,
+        Action::ToggleTheme,
+        Action::Refresh,
+        Action::OpenSettings,
+        Action::Search,
+        Action::ShowShortcuts
+    ];
+
+    /// Name of the action shown in the settings
+    pub fn label(&self, strings: &Strings) -> &'static str {
+        strings.action_labels[*self as usize]
+    }
+}
+
+/// Shortcuts are stored as text, like "Ctrl+F" or "F2"
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Keybindings {
+    pub next_week: String,
+    pub previous_week: String,
+    pub current_week: String,
+    pub toggle_theme: String,
+    pub refresh: String,
+    pub open_settings: String,
+    pub search: String,
+    pub show_shortcuts: String
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self {
+            next_week: "D".into(),
+            previous_week: "A".into(),
+            current_week: "S".into(),
+            toggle_theme: "F2".into(),
+            refresh: "F5".into(),
+            open_settings: "F3".into(),
+            search: "Ctrl+F".into(),
+            show_shortcuts: "F1".into()
+        }
+    }
+}
+
+impl Keybindings {
+    /// Shortcut of `action`, empty when it has none
+    pub fn get(&self, action: Action) -> &str {
+        match action {
+            Action::NextWeek => &self.next_week,
+            Action::PreviousWeek => &self.previous_week,
+            Action::CurrentWeek => &self.current_week,
+            Action::ToggleTheme => &self.toggle_theme,
+            Action::Refresh => &self.refresh,
+            Action::OpenSettings => &self.open_settings,
+            Action::Search => &self.search,
+            Action::ShowShortcuts => &self.show_shortcuts,
+        }
+    }
+
+    /// Replaces the shortcut of `action`
+    pub fn set(&mut self, action: Action, shortcut: String) {
+        let binding = match action {
+            Action::NextWeek => &mut self.next_week,
+            Action::PreviousWeek => &mut self.previous_week,
+            Action::CurrentWeek => &mut self.current_week,
+            Action::ToggleTheme => &mut self.toggle_theme,
+            Action::Refresh => &mut self.refresh,
+            Action::OpenSettings => &mut self.open_settings,
+            Action::Search => &mut self.search,
+            Action::ShowShortcuts => &mut self.show_shortcuts,
+        };
+        *binding = shortcut;
+    }
+}
@@ -0,0 +1,35 @@
+//! Fetching, parsing and storing of KTU timetables, shared by the GUI app and other tools.
+//!
+//! None of these modules depend on egui, everything drawing the window is in the binary.
+
+/// `Timetable` and `Event`, the `TimetableGetter` which downloads a timetable
+/// by its vidko code and `parse_timetable` for any iCalendar file
+pub mod timetable;
+/// Expansion of RRULE recurrences
+pub mod recurrence;
+/// Versioned JSON and TOML forms of a `Timetable`
+pub mod serialization;
+/// Writing events and tasks back into iCalendar files
+pub mod ics;
+/// Settings, their profiles and the files they are kept in
+pub mod config;
+/// The stored shortcut of every app action, as text like "Ctrl+F"
+pub mod keybindings;
+/// Lithuanian and English texts of the interface
+pub mod i18n;
+/// The user's own events, tasks, notes and attendance
+pub mod user_data;
+/// Contact hours and weekly load of a timetable
+pub mod statistics;
+/// The HTTP API used by the headless server mode
+pub mod server;
+/// Conversions between UTC and Europe/Vilnius time
+pub mod timezone;
+/// The getter and stores which the app and server mode run with
+pub mod environment;
+/// Date helpers, like finding the current week with the weekend rollover,
+/// and `WeekScroll` which turns scrolling into week switches
+pub mod utils;
+
+#[cfg(test)]
+mod test_utils;
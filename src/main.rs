@@ -1,21 +1,16 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod timetable;
 mod app;
-mod config;
 mod events_table;
-mod utils;
+mod semester_overview;
+mod shortcuts;
 mod platforms;
-mod environment;
 
 #[macro_use]
 extern crate lazy_static;
 
-use config::TomlConfigStore;
-use environment::Environment;
-use timetable::BlockingTimetableGetter;
+use ktu_timetable::{config::{TomlConfigStore, ConfigStore, data_dir}, environment::Environment, timetable::BlockingTimetableGetter, user_data::TomlUserDataStore, server};
 
-// TODO: show errors when loading config
 // TODO: Settings menu
 // TODO: use "confy" for config loading?
 // TODO: Setup pipeline
@@ -26,25 +21,50 @@ fn main() {
     //     vidko: None//Some("E1810".into())
     // });
 
-    let timetable_getter = BlockingTimetableGetter::default();
+    let http_config = config_store.load().map(|loaded| loaded.config.http).unwrap_or_default();
+    // The app shows this error again in its settings, when it gives the getter the same HTTP settings
+    let timetable_getter = BlockingTimetableGetter::new(http_config).unwrap_or_else(|e| {
+        eprintln!("Invalid HTTP settings, using the defaults: {}", e);
+        BlockingTimetableGetter::default()
+    }).with_cache_file(data_dir().join("timetable_cache.json"));
     // let timetable_getter = DummyTimetableGetter::new(Timetable {
     //     events: vec![
     //         Event {
     //             category: timetable::EventCategory::Default,
-    //             date: NaiveDate::from_ymd_opt(2023, 1, 30).unwrap(),
-    //             start_time: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
-    //             end_time: NaiveTime::from_hms_opt(10, 30, 0).unwrap(),
+    //             start: NaiveDate::from_ymd_opt(2023, 1, 30).unwrap().and_hms_opt(9, 0, 0).unwrap(),
+    //             end: NaiveDate::from_ymd_opt(2023, 1, 30).unwrap().and_hms_opt(10, 30, 0).unwrap(),
     //             description: "Foobarbaz".into(),
     //             summary: "P123B123 Dummy module".into(),
     //             location: "XI r.-521".into(),
-    //             module_name: Some("Dummy module".into())
+    //             module_name: Some("Dummy module".into()),
+    //             module_code: Some("P123B123".into()),
+    //             note: None,
+    //             attendance: None,
+    //             personal_id: None
     //         }
     //     ]
     // });
 
-    platforms::run_windows_app(Environment {
+    let env = Environment {
         timetable_getter: Box::new(timetable_getter),
-        config_store: Box::new(config_store)
-    })
+        config_store: Box::new(config_store),
+        user_data_store: Box::new(TomlUserDataStore::default())
+    };
+
+    // "--serve [address] [--cors]" runs without a window, serving the timetable over HTTP
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--serve") {
+        let address = args.get(i + 1)
+            .filter(|address| !address.starts_with("--"))
+            .map_or(server::DEFAULT_ADDRESS, |address| address.as_str());
+        let allow_cors = args.iter().any(|arg| arg == "--cors");
+        if let Err(e) = server::run_server(env, address, allow_cors, |message| eprintln!("{}", message)) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    platforms::run_windows_app(env)
 }
 
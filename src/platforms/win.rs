@@ -13,7 +13,8 @@ use windows::{
     },
 };
 
-use crate::{environment::Environment, app::MainApp};
+use ktu_timetable::environment::Environment;
+use crate::app::MainApp;
 
 // Yoinked from https://github.com/emilk/egui/issues/920#issuecomment-1364446538
 fn load_app_icon() -> IconData {
@@ -119,7 +120,7 @@ pub fn run_windows_app(env: Environment) {
     native_options.min_window_size = Some(egui::vec2(480.0, 320.0));
     native_options.initial_window_size = Some(egui::vec2(500.0, 320.0));
     native_options.icon_data = Some(load_app_icon());
-    let mut app = MainApp::new(env.config_store,  env.timetable_getter);
+    let mut app = MainApp::new(env.config_store, env.user_data_store, env.timetable_getter);
 
     eframe::run_native(
         "KTU timetable",
@@ -0,0 +1,508 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Utc, Weekday};
+
+use crate::{timezone::{utc_to_vilnius, vilnius_to_utc}, utils::week_monday};
+
+// Rules without COUNT or UNTIL are only expanded this far from DTSTART
+const UNBOUNDED_LIMIT_DAYS: i64 = 366;
+// Safety net against rules which would produce a huge amount of occurrences
+const MAX_OCCURRENCES: usize = 2000;
+// Rules which never match anything, like the 30th of february, would loop forever without this
+const MAX_PERIODS: u32 = 10_000;
+
+/// FREQ of a rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly
+}
+
+/// Subset of RFC 5545 RRULE, BYSETPOS and the sub-daily frequencies are not supported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    pub frequency: Frequency,
+    pub interval: u32,
+    pub count: Option<u32>,
+    /// As written in the rule, in UTC when `until_is_utc`. A DATE is the end of that day.
+    pub until: Option<NaiveDateTime>,
+    pub until_is_utc: bool,
+    /// Weekday with an optional ordinal, "-1FR" is the last friday
+    pub by_day: Vec<(Option<i32>, Weekday)>,
+    pub by_month_day: Vec<i32>,
+    pub by_month: Vec<u32>
+}
+
+fn parse_weekday(text: &str) -> Option<Weekday> {
+    match text {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None
+    }
+}
+
+/// Parses a DATE or DATE-TIME value without converting it, also telling whether it is in UTC ("Z" suffix).
+/// DATE values are at midnight.
+pub fn parse_ical_value(value: &str) -> Option<(NaiveDateTime, bool)> {
+    if !value.contains('T') {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some((date.and_hms_opt(0, 0, 0)?, false));
+    }
+
+    let datetime = NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S").ok()?;
+    Some((datetime, value.ends_with('Z')))
+}
+
+/// Converts a time to Vilnius time if it is in UTC
+pub fn to_vilnius(datetime: NaiveDateTime, is_utc: bool) -> NaiveDateTime {
+    if is_utc {
+        utc_to_vilnius(DateTime::<Utc>::from_utc(datetime, Utc))
+    } else {
+        datetime
+    }
+}
+
+/// Parses a DATE or DATE-TIME value, UTC times ("Z" suffix or `is_utc`) are converted to Vilnius time
+pub fn parse_ical_datetime(value: &str, is_utc: bool) -> Option<NaiveDateTime> {
+    let (datetime, is_z) = parse_ical_value(value)?;
+    // DATE values have no time of day to convert
+    Some(to_vilnius(datetime, value.contains('T') && (is_utc || is_z)))
+}
+
+/// Parses a DURATION value, like "PT1H30M", "P1D" or "-P2W"
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let (sign, value) = match value.strip_prefix('-') {
+        Some(value) => (-1, value),
+        None => (1, value.trim_start_matches('+'))
+    };
+    let value = value.strip_prefix('P')?;
+
+    let mut seconds: i64 = 0;
+    let mut number = String::new();
+    let mut is_time = false;
+    for c in value.chars() {
+        let unit = match (c, is_time) {
+            ('0'..='9', _) => {
+                number.push(c);
+                continue;
+            },
+            ('T', false) if number.is_empty() => {
+                is_time = true;
+                continue;
+            },
+            ('W', false) => 7 * 24 * 3600,
+            ('D', false) => 24 * 3600,
+            ('H', true) => 3600,
+            ('M', true) => 60,
+            ('S', true) => 1,
+            _ => return None
+        };
+        let amount: i64 = number.parse().ok()?;
+        number.clear();
+        seconds = seconds.checked_add(amount.checked_mul(unit)?)?;
+    }
+    // `Duration::seconds` panics on values which don't fit into milliseconds
+    if !number.is_empty() || seconds > i64::MAX / 1000 {
+        return None;
+    }
+    Some(Duration::seconds(sign * seconds))
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    (28..=31).rev()
+        .find(|day| NaiveDate::from_ymd_opt(year, month, *day).is_some())
+        .unwrap_or(0)
+}
+
+fn add_months(year: i32, month: u32, months: u32) -> Option<(i32, u32)> {
+    let index = year as i64 * 12 + (month as i64 - 1) + months as i64;
+    Some((i32::try_from(index / 12).ok()?, (index % 12) as u32 + 1))
+}
+
+impl RecurrenceRule {
+    /// Parses the value of an RRULE property, None when it's invalid or uses unsupported parts
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut rule = RecurrenceRule {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            until_is_utc: false,
+            by_day: vec![],
+            by_month_day: vec![],
+            by_month: vec![]
+        };
+        let mut frequency = None;
+
+        for part in value.split(';') {
+            let (name, value) = part.split_once('=')?;
+            match name {
+                "FREQ" => {
+                    frequency = Some(match value {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return None
+                    });
+                },
+                "INTERVAL" => rule.interval = value.parse().ok().filter(|interval| *interval > 0)?,
+                "COUNT" => rule.count = Some(value.parse().ok()?),
+                "UNTIL" => {
+                    let (until, is_utc) = parse_ical_value(value)?;
+                    // A DATE includes the whole day
+                    let until = if value.contains('T') { until } else { until.date().and_hms_opt(23, 59, 59)? };
+                    rule.until = Some(until);
+                    rule.until_is_utc = is_utc;
+                },
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        if !day.is_char_boundary(day.len().checked_sub(2)?) {
+                            return None;
+                        }
+                        let (ordinal, weekday) = day.split_at(day.len() - 2);
+                        let ordinal = if ordinal.is_empty() {
+                            None
+                        } else {
+                            Some(ordinal.trim_start_matches('+').parse().ok().filter(|n: &i32| *n != 0 && (-53..=53).contains(n))?)
+                        };
+                        rule.by_day.push((ordinal, parse_weekday(weekday)?));
+                    }
+                },
+                "BYMONTHDAY" => {
+                    for day in value.split(',') {
+                        rule.by_month_day.push(day.parse().ok().filter(|day: &i32| *day != 0 && (-31..=31).contains(day))?);
+                    }
+                },
+                "BYMONTH" => {
+                    for month in value.split(',') {
+                        rule.by_month.push(month.parse().ok().filter(|month| (1..=12).contains(month))?);
+                    }
+                },
+                // Weeks are always assumed to start on monday
+                "WKST" => {},
+                _ => return None
+            }
+        }
+
+        rule.frequency = frequency?;
+        Some(rule)
+    }
+
+    fn matches_weekday(&self, date: NaiveDate) -> bool {
+        self.by_day.is_empty() || self.by_day.iter().any(|(_, weekday)| *weekday == date.weekday())
+    }
+
+    fn matches_month_day(&self, date: NaiveDate) -> bool {
+        if self.by_month_day.is_empty() {
+            return true;
+        }
+        let days = days_in_month(date.year(), date.month()) as i32;
+        self.by_month_day.iter().any(|day| {
+            let day = if *day < 0 { days + day + 1 } else { *day };
+            day == date.day() as i32
+        })
+    }
+
+    fn matches_month(&self, date: NaiveDate) -> bool {
+        self.by_month.is_empty() || self.by_month.contains(&date.month())
+    }
+
+    // BYDAY applied to all dates of a month or a year, ordinals count inside of those dates
+    fn weekday_dates(&self, dates: &[NaiveDate]) -> Vec<NaiveDate> {
+        let mut result = vec![];
+        for (ordinal, weekday) in &self.by_day {
+            let matching: Vec<NaiveDate> = dates.iter()
+                .copied()
+                .filter(|date| date.weekday() == *weekday)
+                .collect();
+            match ordinal {
+                Some(n) if *n > 0 => result.extend(matching.get(*n as usize - 1)),
+                Some(n) if *n < 0 => result.extend(matching.len().checked_sub(n.unsigned_abs() as usize).and_then(|i| matching.get(i))),
+                _ => result.extend(matching)
+            }
+        }
+        result
+    }
+
+    // Candidate dates inside of a single month, `default_day` is used when there are no BYxxx parts
+    fn month_dates(&self, year: i32, month: u32, default_day: u32) -> Vec<NaiveDate> {
+        let days = days_in_month(year, month);
+        let month_dates: Vec<NaiveDate> = (1..=days)
+            .filter_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+            .collect();
+
+        let mut dates: Vec<NaiveDate> = if !self.by_month_day.is_empty() {
+            month_dates.into_iter()
+                .filter(|date| self.matches_month_day(*date) && self.matches_weekday(*date))
+                .collect()
+        } else if !self.by_day.is_empty() {
+            self.weekday_dates(&month_dates)
+        } else {
+            NaiveDate::from_ymd_opt(year, month, default_day).into_iter().collect()
+        };
+
+        dates.sort();
+        dates.dedup();
+        dates
+    }
+
+    // Candidate dates inside of a single year
+    fn year_dates(&self, year: i32, start: NaiveDate) -> Vec<NaiveDate> {
+        let mut dates: Vec<NaiveDate> = if !self.by_month.is_empty() {
+            self.by_month.iter()
+                .flat_map(|month| self.month_dates(year, *month, start.day()))
+                .collect()
+        } else if !self.by_month_day.is_empty() {
+            (1..=12).flat_map(|month| self.month_dates(year, month, start.day())).collect()
+        } else if !self.by_day.is_empty() {
+            // Without BYMONTH, "20MO" is the 20th monday of the whole year
+            let year_dates: Vec<NaiveDate> = (1..=12)
+                .flat_map(|month| (1..=days_in_month(year, month)).filter_map(move |day| NaiveDate::from_ymd_opt(year, month, day)))
+                .collect();
+            self.weekday_dates(&year_dates)
+        } else {
+            self.month_dates(year, start.month(), start.day())
+        };
+
+        dates.sort();
+        dates.dedup();
+        dates
+    }
+
+    // Dates of the `index`th period (day, week, month or year) after the start.
+    // None when the period is past the dates which can be represented.
+    fn period_dates(&self, start: NaiveDate, index: u32) -> Option<(NaiveDate, Vec<NaiveDate>)> {
+        let step = index.checked_mul(self.interval)?;
+        match self.frequency {
+            Frequency::Daily => {
+                let date = start.checked_add_signed(Duration::days(step as i64))?;
+                let matches = self.matches_weekday(date) && self.matches_month_day(date) && self.matches_month(date);
+                Some((date, if matches { vec![date] } else { vec![] }))
+            },
+            Frequency::Weekly => {
+                let monday = week_monday(start.iso_week()).checked_add_signed(Duration::weeks(step as i64))?;
+                let weekdays = if self.by_day.is_empty() { vec![start.weekday()] } else { self.by_day.iter().map(|(_, weekday)| *weekday).collect() };
+                let mut dates: Vec<NaiveDate> = weekdays.iter()
+                    .filter_map(|weekday| monday.checked_add_signed(Duration::days(weekday.num_days_from_monday() as i64)))
+                    .filter(|date| self.matches_month(*date))
+                    .collect();
+                dates.sort();
+                dates.dedup();
+                Some((monday, dates))
+            },
+            Frequency::Monthly => {
+                let (year, month) = add_months(start.year(), start.month(), step)?;
+                let first_day = NaiveDate::from_ymd_opt(year, month, 1)?;
+                let mut dates = self.month_dates(year, month, start.day());
+                dates.retain(|date| self.matches_month(*date));
+                Some((first_day, dates))
+            },
+            Frequency::Yearly => {
+                let year = start.year().checked_add(i32::try_from(step).ok()?)?;
+                let first_day = NaiveDate::from_ymd_opt(year, 1, 1)?;
+                Some((first_day, self.year_dates(year, start)))
+            }
+        }
+    }
+
+    /// Start times of all occurrences, DTSTART always counts as the first one.
+    /// Occurrences are in the same time as `start`, which is UTC when `start_is_utc`.
+    pub fn occurrences(&self, start: NaiveDateTime, start_is_utc: bool) -> Vec<NaiveDateTime> {
+        let until = self.until.map(|until| match (self.until_is_utc, start_is_utc) {
+            (true, false) => utc_to_vilnius(DateTime::<Utc>::from_utc(until, Utc)),
+            (false, true) => vilnius_to_utc(until).naive_utc(),
+            _ => until
+        });
+        let limit = match (until, self.count) {
+            (Some(until), _) => until,
+            (None, Some(_)) => NaiveDateTime::MAX,
+            (None, None) => start.checked_add_signed(Duration::days(UNBOUNDED_LIMIT_DAYS)).unwrap_or(NaiveDateTime::MAX)
+        };
+        let max_count = self.count.map_or(MAX_OCCURRENCES, |count| (count as usize).min(MAX_OCCURRENCES));
+
+        let mut occurrences = vec![start];
+        let mut index = 0;
+        'periods: while occurrences.len() < max_count && index < MAX_PERIODS {
+            let (period_start, dates) = match self.period_dates(start.date(), index) {
+                Some(period) => period,
+                None => break
+            };
+            if period_start.and_time(start.time()) > limit {
+                break;
+            }
+
+            for date in dates {
+                let occurrence = date.and_time(start.time());
+                if occurrence <= start {
+                    continue;
+                }
+                if occurrence > limit || occurrences.len() >= max_count {
+                    break 'periods;
+                }
+                occurrences.push(occurrence);
+            }
+            index += 1;
+        }
+
+        occurrences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::at;
+
+    use super::*;
+
+    fn dates(rule: &str, start: NaiveDateTime) -> Vec<NaiveDate> {
+        RecurrenceRule::parse(rule).unwrap()
+            .occurrences(start, false)
+            .iter()
+            .map(|occurrence| occurrence.date())
+            .collect()
+    }
+
+    fn ymd(dates: &[(i32, u32, u32)]) -> Vec<NaiveDate> {
+        dates.iter().map(|(year, month, day)| NaiveDate::from_ymd_opt(*year, *month, *day).unwrap()).collect()
+    }
+
+    // Examples from RFC 5545 section 3.8.5.3, with floating instead of America/New_York times
+
+    #[test]
+    fn weekly_for_10_occurrences() {
+        assert_eq!(dates("FREQ=WEEKLY;COUNT=10", at(1997, 9, 2, 9, 0)), ymd(&[
+            (1997, 9, 2), (1997, 9, 9), (1997, 9, 16), (1997, 9, 23), (1997, 9, 30),
+            (1997, 10, 7), (1997, 10, 14), (1997, 10, 21), (1997, 10, 28), (1997, 11, 4)
+        ]));
+    }
+
+    #[test]
+    fn weekly_on_tuesday_and_thursday() {
+        let expected = ymd(&[
+            (1997, 9, 2), (1997, 9, 4), (1997, 9, 9), (1997, 9, 11), (1997, 9, 16),
+            (1997, 9, 18), (1997, 9, 23), (1997, 9, 25), (1997, 9, 30), (1997, 10, 2)
+        ]);
+        assert_eq!(dates("FREQ=WEEKLY;UNTIL=19971007T000000Z;WKST=SU;BYDAY=TU,TH", at(1997, 9, 2, 9, 0)), expected);
+        assert_eq!(dates("FREQ=WEEKLY;COUNT=10;WKST=SU;BYDAY=TU,TH", at(1997, 9, 2, 9, 0)), expected);
+    }
+
+    #[test]
+    fn every_other_week_on_monday_wednesday_and_friday() {
+        assert_eq!(dates("FREQ=WEEKLY;INTERVAL=2;UNTIL=19971224T000000Z;WKST=SU;BYDAY=MO,WE,FR", at(1997, 9, 1, 9, 0)), ymd(&[
+            (1997, 9, 1), (1997, 9, 3), (1997, 9, 5), (1997, 9, 15), (1997, 9, 17),
+            (1997, 9, 19), (1997, 9, 29), (1997, 10, 1), (1997, 10, 3), (1997, 10, 13),
+            (1997, 10, 15), (1997, 10, 17), (1997, 10, 27), (1997, 10, 29), (1997, 10, 31),
+            (1997, 11, 10), (1997, 11, 12), (1997, 11, 14), (1997, 11, 24), (1997, 11, 26),
+            (1997, 11, 28), (1997, 12, 8), (1997, 12, 10), (1997, 12, 12), (1997, 12, 22)
+        ]));
+    }
+
+    #[test]
+    fn monthly_on_first_friday() {
+        assert_eq!(dates("FREQ=MONTHLY;COUNT=10;BYDAY=1FR", at(1997, 9, 5, 9, 0)), ymd(&[
+            (1997, 9, 5), (1997, 10, 3), (1997, 11, 7), (1997, 12, 5), (1998, 1, 2),
+            (1998, 2, 6), (1998, 3, 6), (1998, 4, 3), (1998, 5, 1), (1998, 6, 5)
+        ]));
+    }
+
+    #[test]
+    fn monthly_on_last_friday() {
+        assert_eq!(dates("FREQ=MONTHLY;COUNT=4;BYDAY=-1FR", at(2023, 1, 27, 9, 0)), ymd(&[
+            (2023, 1, 27), (2023, 2, 24), (2023, 3, 31), (2023, 4, 28)
+        ]));
+        assert_eq!(dates("FREQ=MONTHLY;COUNT=6;BYDAY=-2MO", at(1997, 9, 22, 9, 0)), ymd(&[
+            (1997, 9, 22), (1997, 10, 20), (1997, 11, 17), (1997, 12, 22), (1998, 1, 19), (1998, 2, 16)
+        ]));
+    }
+
+    #[test]
+    fn monthly_on_first_and_last_sunday() {
+        assert_eq!(dates("FREQ=MONTHLY;INTERVAL=2;COUNT=10;BYDAY=1SU,-1SU", at(1997, 9, 7, 9, 0)), ymd(&[
+            (1997, 9, 7), (1997, 9, 28), (1997, 11, 2), (1997, 11, 30), (1998, 1, 4),
+            (1998, 1, 25), (1998, 3, 1), (1998, 3, 29), (1998, 5, 3), (1998, 5, 31)
+        ]));
+    }
+
+    #[test]
+    fn count_and_until() {
+        assert_eq!(dates("FREQ=DAILY;COUNT=10", at(1997, 9, 2, 9, 0)).len(), 10);
+        let until = dates("FREQ=DAILY;UNTIL=19971224T000000Z", at(1997, 9, 2, 9, 0));
+        assert_eq!(until.len(), 113);
+        assert_eq!(until.last(), Some(&NaiveDate::from_ymd_opt(1997, 12, 23).unwrap()));
+        // Whichever ends first wins
+        assert_eq!(dates("FREQ=DAILY;COUNT=3;UNTIL=19971224T000000Z", at(1997, 9, 2, 9, 0)).len(), 3);
+        assert_eq!(dates("FREQ=DAILY;COUNT=100;UNTIL=19970905T000000Z", at(1997, 9, 2, 9, 0)).len(), 3);
+    }
+
+    #[test]
+    fn date_until_includes_that_day() {
+        assert_eq!(dates("FREQ=DAILY;UNTIL=20230203", at(2023, 2, 1, 9, 0)), ymd(&[
+            (2023, 2, 1), (2023, 2, 2), (2023, 2, 3)
+        ]));
+    }
+
+    #[test]
+    fn yearly_by_day_without_by_month() {
+        // Every 20th monday of the year
+        assert_eq!(dates("FREQ=YEARLY;COUNT=3;BYDAY=20MO", at(1997, 5, 19, 9, 0)), ymd(&[
+            (1997, 5, 19), (1998, 5, 18), (1999, 5, 17)
+        ]));
+        assert_eq!(dates("FREQ=YEARLY;COUNT=4;BYMONTH=3;BYDAY=TH", at(1997, 3, 13, 9, 0)), ymd(&[
+            (1997, 3, 13), (1997, 3, 20), (1997, 3, 27), (1998, 3, 5)
+        ]));
+    }
+
+    #[test]
+    fn utc_rules_keep_utc_time() {
+        // UNTIL in Vilnius time is compared in UTC, 2023-03-27 09:00 Vilnius is 06:00 UTC
+        let rule = RecurrenceRule::parse("FREQ=DAILY;UNTIL=20230327T090000").unwrap();
+        assert_eq!(rule.occurrences(at(2023, 3, 25, 6, 0), true), vec![
+            at(2023, 3, 25, 6, 0), at(2023, 3, 26, 6, 0), at(2023, 3, 27, 6, 0)
+        ]);
+        let rule = RecurrenceRule::parse("FREQ=DAILY;UNTIL=20230327T055959").unwrap();
+        assert_eq!(rule.occurrences(at(2023, 3, 25, 6, 0), true).len(), 2);
+    }
+
+    #[test]
+    fn huge_intervals_do_not_overflow() {
+        let start = at(2023, 2, 1, 9, 0);
+        for rule in [
+            "FREQ=DAILY;INTERVAL=4294967295;COUNT=5",
+            "FREQ=WEEKLY;INTERVAL=4294967295;COUNT=5",
+            "FREQ=MONTHLY;INTERVAL=4294967295;COUNT=5",
+            "FREQ=YEARLY;INTERVAL=4294967295;COUNT=5",
+            "FREQ=YEARLY;INTERVAL=300000;COUNT=5",
+            "FREQ=MONTHLY;INTERVAL=2147483647"
+        ] {
+            assert_eq!(RecurrenceRule::parse(rule).unwrap().occurrences(start, false), vec![start], "{}", rule);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_parts() {
+        assert_eq!(RecurrenceRule::parse("FREQ=YEARLY;BYMONTH=13"), None);
+        assert_eq!(RecurrenceRule::parse("FREQ=MONTHLY;BYMONTHDAY=0"), None);
+        assert_eq!(RecurrenceRule::parse("FREQ=WEEKLY;BYDAY=ŽX"), None);
+        assert_eq!(RecurrenceRule::parse("FREQ=WEEKLY;INTERVAL=0"), None);
+        assert_eq!(RecurrenceRule::parse("FREQ=HOURLY"), None);
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("PT1H30M"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("P1D"), Some(Duration::days(1)));
+        assert_eq!(parse_duration("P2W"), Some(Duration::weeks(2)));
+        assert_eq!(parse_duration("P1DT2H"), Some(Duration::hours(26)));
+        assert_eq!(parse_duration("-PT15M"), Some(Duration::minutes(-15)));
+        assert_eq!(parse_duration("PT"), Some(Duration::zero()));
+        assert_eq!(parse_duration("P1H"), None);
+        assert_eq!(parse_duration("PT99999999999999999999S"), None);
+        assert_eq!(parse_duration("1H"), None);
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use eframe::egui;
+use chrono::{Datelike, NaiveDate, IsoWeek, Duration};
+use egui::{Color32, Visuals};
+use ktu_timetable::{timetable::Event, utils::week_monday, i18n::Language};
+
+const ROW_HEIGHT: f32 = 22.0;
+const LABEL_WIDTH: f32 = 110.0;
+
+pub struct SemesterOverview<'a> {
+    pub highlighted_week: Option<IsoWeek>,
+    pub today: Option<NaiveDate>,
+    pub language: Language,
+    pub events: &'a [Event]
+}
+
+#[inline]
+fn mix_color(from: Color32, to: Color32, amount: f32) -> Color32 {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount) as u8;
+    return Color32::from_rgb(
+        mix(from.r(), to.r()),
+        mix(from.g(), to.g()),
+        mix(from.b(), to.b())
+    );
+}
+
+impl<'a> SemesterOverview<'a> {
+    pub fn new(events: &'a [Event]) -> Self {
+        Self {
+            highlighted_week: None,
+            today: None,
+            language: Language::default(),
+            events
+        }
+    }
+
+    #[inline]
+    fn empty_fill(&self, visuals: &Visuals) -> Color32 {
+        visuals.widgets.noninteractive.bg_fill
+    }
+
+    #[inline]
+    fn busy_fill(&self, visuals: &Visuals) -> Color32 {
+        visuals.selection.bg_fill
+    }
+
+    // Returns the week which was clicked on
+    pub fn show(self, ui: &mut egui::Ui) -> Option<IsoWeek> {
+        use egui::*;
+
+        let first_date = self.events.iter().map(|e| e.start.date()).min()?;
+        let last_date = self.events.iter().filter_map(|e| e.dates().last().copied()).max()?;
+
+        let mut minutes_per_day: HashMap<NaiveDate, i64> = HashMap::new();
+        for event in self.events {
+            for date in event.dates() {
+                if let Some((start, end)) = event.clipped_to(date) {
+                    *minutes_per_day.entry(date).or_insert(0) += (end - start).num_minutes();
+                }
+            }
+        }
+        let max_minutes = minutes_per_day.values().copied().max().unwrap_or(1).max(1);
+
+        let strings = self.language.strings();
+        let text_size = egui::TextStyle::Body.resolve(ui.style()).size;
+        let mut clicked_week = None;
+
+        ScrollArea::vertical().show(ui, |ui| {
+            let mut monday = week_monday(first_date.iso_week());
+            while monday <= last_date {
+                let week = monday.iso_week();
+                let (rect, response) = ui.allocate_exact_size(vec2(ui.available_width(), ROW_HEIGHT), Sense::click());
+
+                let visuals = ui.visuals();
+                let painter = ui.painter();
+                let text_color = visuals.widgets.active.fg_stroke.color;
+                if self.highlighted_week.map(week_monday) == Some(monday) || response.hovered() {
+                    painter.rect_filled(rect, Rounding::none(), visuals.widgets.hovered.bg_fill);
+                }
+
+                painter.text(
+                    rect.left_center() + vec2(4.0, 0.0),
+                    Align2::LEFT_CENTER,
+                    format!("{:02} {} {}", week.week(), strings.week_short, monday.format(strings.short_date_format)),
+                    FontId::proportional(text_size*0.85),
+                    text_color
+                );
+
+                let column_width = (rect.width() - LABEL_WIDTH) / 5.0;
+                for i in 0..5 {
+                    let day = monday + Duration::days(i);
+                    let minutes = minutes_per_day.get(&day).copied().unwrap_or(0);
+                    let amount = minutes as f32 / max_minutes as f32;
+                    let cell = Rect::from_min_size(
+                        rect.left_top() + vec2(LABEL_WIDTH + column_width * i as f32, 0.0),
+                        vec2(column_width, ROW_HEIGHT)
+                    ).shrink(2.0);
+                    painter.rect_filled(cell, Rounding::from(3.0), mix_color(self.empty_fill(visuals), self.busy_fill(visuals), amount));
+                    if self.today == Some(day) {
+                        painter.rect_stroke(cell, Rounding::from(3.0), (2.0, text_color));
+                    }
+                }
+
+                if response.clicked() {
+                    clicked_week = Some(week);
+                }
+
+                monday = monday + Duration::days(7);
+            }
+        });
+
+        clicked_week
+    }
+}
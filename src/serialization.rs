@@ -0,0 +1,180 @@
+use std::{error::Error, fmt};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::timetable::{Event, Timetable};
+
+/// Increase this when the serialized form of `Timetable`, `Event` or `EventCategory` changes
+/// in a way older versions can't read, and upgrade the older versions in `Timetable::from_value`
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct VersionedTimetableRef<'a> {
+    version: u32,
+    events: &'a [Event]
+}
+
+#[derive(Deserialize)]
+struct VersionedTimetable {
+    events: Vec<Event>
+}
+
+/// Why a timetable couldn't be written or read back
+#[derive(Debug)]
+pub enum FormatError {
+    MissingVersion,
+    /// Written by a newer version of the app
+    UnsupportedVersion(u32),
+    JsonError(serde_json::Error),
+    TomlDeError(toml::de::Error),
+    TomlSerError(toml::ser::Error)
+}
+
+impl Error for FormatError {}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use FormatError::*;
+        match self {
+            MissingVersion => write!(f, "Format version is missing"),
+            UnsupportedVersion(version) => write!(f, "Unsupported format version {}", version),
+            JsonError(e) => write!(f, "Json error: {}", e),
+            TomlDeError(e) => write!(f, "Toml error: {}", e),
+            TomlSerError(e) => write!(f, "Toml error: {}", e)
+        }
+    }
+}
+
+impl Timetable {
+    fn versioned(&self) -> VersionedTimetableRef<'_> {
+        VersionedTimetableRef {
+            version: FORMAT_VERSION,
+            events: &self.events
+        }
+    }
+
+    // For storing a timetable inside other files, like the download cache
+    pub(crate) fn to_value(&self) -> Result<Value, FormatError> {
+        serde_json::to_value(self.versioned())
+            .map_err(FormatError::JsonError)
+    }
+
+    // Both JSON and TOML are read through a JSON value, so upgrades only need to be written once
+    pub(crate) fn from_value(value: Value) -> Result<Self, FormatError> {
+        let version = value.get("version")
+            .and_then(Value::as_u64)
+            .ok_or(FormatError::MissingVersion)?;
+        if version == 0 || version > FORMAT_VERSION as u64 {
+            return Err(FormatError::UnsupportedVersion(version as u32));
+        }
+
+        // Older versions get upgraded here, before decoding. There are none yet.
+        let timetable: VersionedTimetable = serde_json::from_value(value)
+            .map_err(FormatError::JsonError)?;
+
+        Ok(Timetable { events: timetable.events })
+    }
+
+    /// Pretty printed, with `FORMAT_VERSION` alongside the events
+    pub fn to_json(&self) -> Result<String, FormatError> {
+        serde_json::to_string_pretty(&self.versioned())
+            .map_err(FormatError::JsonError)
+    }
+
+    /// Reads what `to_json` of this or an older version wrote
+    pub fn from_json(text: &str) -> Result<Self, FormatError> {
+        let value = serde_json::from_str(text)
+            .map_err(FormatError::JsonError)?;
+        Self::from_value(value)
+    }
+
+    /// Same as `to_json`, in TOML
+    pub fn to_toml(&self) -> Result<String, FormatError> {
+        toml::to_string_pretty(&self.versioned())
+            .map_err(FormatError::TomlSerError)
+    }
+
+    /// Reads what `to_toml` of this or an older version wrote
+    pub fn from_toml(text: &str) -> Result<Self, FormatError> {
+        let value: toml::Value = toml::from_str(text)
+            .map_err(FormatError::TomlDeError)?;
+        let value = serde_json::to_value(value)
+            .map_err(FormatError::JsonError)?;
+        Self::from_value(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::timetable::EventCategory;
+
+    use super::*;
+
+    fn timetable() -> Timetable {
+        let start = NaiveDate::from_ymd_opt(2023, 2, 6).unwrap().and_hms_opt(8, 30, 0).unwrap();
+        Timetable {
+            events: vec![
+                Event {
+                    category: EventCategory::Yellow,
+                    start,
+                    end: start + chrono::Duration::minutes(90),
+                    description: "Lecture".to_string(),
+                    summary: "P175B123 Databases".to_string(),
+                    location: "XI r.-101".to_string(),
+                    module_name: Some("Databases".to_string()),
+                    module_code: Some("P175B123".to_string()),
+                    note: Some("Bring a laptop".to_string()),
+                    attendance: None,
+                    personal_id: None
+                },
+                Event {
+                    category: EventCategory::Personal,
+                    start,
+                    end: start,
+                    description: String::new(),
+                    summary: "Study".to_string(),
+                    location: String::new(),
+                    module_name: None,
+                    module_code: None,
+                    note: None,
+                    attendance: None,
+                    personal_id: Some(3)
+                }
+            ]
+        }
+    }
+
+    // `Event` has no `PartialEq`, so timetables are compared through their JSON form
+    fn assert_same(a: &Timetable, b: &Timetable) {
+        assert_eq!(a.to_json().unwrap(), b.to_json().unwrap());
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let timetable = timetable();
+        let text = timetable.to_json().unwrap();
+        assert!(text.contains("\"version\": 1"));
+        assert_same(&Timetable::from_json(&text).unwrap(), &timetable);
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let timetable = timetable();
+        let text = timetable.to_toml().unwrap();
+        assert!(text.starts_with("version = 1"));
+        assert_same(&Timetable::from_toml(&text).unwrap(), &timetable);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let future = format!(r#"{{ "version": {}, "events": [] }}"#, FORMAT_VERSION + 1);
+        assert!(matches!(Timetable::from_json(&future), Err(FormatError::UnsupportedVersion(v)) if v == FORMAT_VERSION + 1));
+        assert!(matches!(Timetable::from_json(r#"{ "version": 0, "events": [] }"#), Err(FormatError::UnsupportedVersion(0))));
+        assert!(matches!(Timetable::from_json(r#"{ "events": [] }"#), Err(FormatError::MissingVersion)));
+        assert!(matches!(Timetable::from_toml("events = []"), Err(FormatError::MissingVersion)));
+        assert!(matches!(Timetable::from_toml("version = 2\nevents = []"), Err(FormatError::UnsupportedVersion(2))));
+    }
+}
@@ -0,0 +1,497 @@
+use std::{io::{self, BufRead, BufReader, Write}, net::{TcpListener, TcpStream}, time::{Duration, Instant}};
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde_json::{json, Value};
+
+use crate::{
+    config::Config,
+    environment::Environment,
+    ics::events_to_ics,
+    timetable::{Event, Timetable, TimetableGetter, GetTimetableError},
+    timezone::vilnius_now,
+    user_data::UserData
+};
+
+/// Address the server listens on when none is given
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+// How long a fetched timetable is served before asking KTU whether it has changed
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+// How long to wait before trying again after a fetch failed
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Keeps the last fetched timetable, so that not every request goes to KTU
+struct TimetableCache {
+    getter: Box<dyn TimetableGetter>,
+    // The active profile's vidko code and its extra ones
+    vidkos: Vec<String>,
+    timetable: Option<Timetable>,
+    // When the timetable should be fetched again, None before the first fetch
+    refresh_at: Option<Instant>
+}
+
+impl TimetableCache {
+    fn new(getter: Box<dyn TimetableGetter>, vidkos: Vec<String>) -> Self {
+        Self {
+            getter,
+            vidkos,
+            timetable: None,
+            refresh_at: None
+        }
+    }
+
+    // Fetches the timetable if it's stale. On failure the old timetable keeps being served,
+    // and the fetch is tried again sooner than usual.
+    fn refresh(&mut self, now: Instant) -> Result<(), GetTimetableError> {
+        if matches!(self.refresh_at, Some(refresh_at) if now < refresh_at) {
+            return Ok(());
+        }
+        match self.getter.get_merged(&self.vidkos) {
+            Ok(timetable) => {
+                self.timetable = Some(timetable);
+                self.refresh_at = Some(now + REFRESH_INTERVAL);
+                Ok(())
+            },
+            Err(e) => {
+                self.refresh_at = Some(now + RETRY_INTERVAL);
+                Err(e)
+            }
+        }
+    }
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String
+}
+
+impl Response {
+    fn json(value: Value) -> Self {
+        Self {
+            status: "200 OK",
+            content_type: "application/json; charset=utf-8",
+            body: value.to_string()
+        }
+    }
+
+    fn error(status: &'static str) -> Self {
+        Self {
+            status,
+            content_type: "application/json; charset=utf-8",
+            body: json!({ "error": status }).to_string()
+        }
+    }
+
+    fn write_to(&self, stream: &mut TcpStream, allow_cors: bool) -> io::Result<()> {
+        let cors = if allow_cors { "Access-Control-Allow-Origin: *\r\n" } else { "" };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+            self.status,
+            self.content_type,
+            self.body.len(),
+            cors
+        )?;
+        stream.write_all(self.body.as_bytes())
+    }
+}
+
+fn event_to_json(event: &Event) -> Value {
+    json!({
+        "start": event.start.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        "end": event.end.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        "summary": event.summary,
+        "module_name": event.module_name,
+        "module_code": event.module_code,
+        "location": event.location,
+        "description": event.description,
+        "category": format!("{:?}", event.category),
+        "note": event.note
+    })
+}
+
+fn events_to_json<'a>(events: impl IntoIterator<Item = &'a Event>) -> Value {
+    Value::Array(events.into_iter().map(event_to_json).collect())
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|digit| digit as u8)
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match bytes.get(i+1..i+3) {
+            Some(&[high, low]) if bytes[i] == b'%' => hex_value(high).zip(hex_value(low)),
+            _ => None
+        };
+        match (bytes[i], escaped) {
+            (_, Some((high, low))) => {
+                decoded.push(high * 16 + low);
+                i += 2;
+            },
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte)
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| percent_decode(value))
+}
+
+// Parses ISO weeks like "2023-W05"
+fn parse_iso_week(text: &str) -> Option<NaiveDate> {
+    let (year, week) = text.split_once("-W")?;
+    NaiveDate::from_isoywd_opt(year.parse().ok()?, week.parse().ok()?, Weekday::Mon)
+}
+
+enum Route {
+    // Monday of the requested week
+    Week(NaiveDate),
+    Today,
+    Next,
+    // Optional module name or code to filter by
+    Events(Option<String>),
+    TimetableJson,
+    Calendar
+}
+
+impl Route {
+    fn parse(path: &str, query: &str) -> Result<Self, Response> {
+        if let Some(week) = path.strip_prefix("/week/") {
+            return parse_iso_week(week).map(Route::Week).ok_or_else(|| Response::error("400 Bad Request"));
+        }
+
+        match path {
+            "/today" => Ok(Route::Today),
+            "/next" => Ok(Route::Next),
+            "/events" => Ok(Route::Events(query_param(query, "module"))),
+            "/timetable.json" => Ok(Route::TimetableJson),
+            "/calendar.ics" => Ok(Route::Calendar),
+            _ => Err(Response::error("404 Not Found"))
+        }
+    }
+}
+
+struct Server {
+    cache: TimetableCache,
+    // Whether browser pages from other origins may read the responses
+    allow_cors: bool,
+    config: Config,
+    user_data: UserData,
+    // Where failures which can't be sent back to a client are reported
+    log: fn(&str)
+}
+
+impl Server {
+    // Events which are shown in the app, with hidden modules left out
+    fn visible_events(&mut self) -> Option<Vec<Event>> {
+        if let Err(e) = self.cache.refresh(Instant::now()) {
+            (self.log)(&format!("Failed to fetch timetable: {}", e));
+        }
+        if let Some(warning) = self.cache.getter.take_warning() {
+            (self.log)(&warning);
+        }
+        let hidden_modules = self.config.profile().map_or(vec![], |profile| profile.hidden_modules.clone());
+        let mut events: Vec<Event> = self.cache.timetable.as_ref()?
+            .events.iter()
+            .filter(|event| !hidden_modules.iter().any(|module| module == event.module_label()))
+            .cloned()
+            .collect();
+        self.user_data.annotate_events(&mut events);
+        Some(events)
+    }
+
+    fn handle(&mut self, method: &str, target: &str) -> Response {
+        if method != "GET" {
+            return Response::error("405 Method Not Allowed");
+        }
+
+        // The route is checked first, so that unknown paths don't make requests to KTU
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let route = match Route::parse(path, query) {
+            Ok(route) => route,
+            Err(response) => return response
+        };
+        let events = match self.visible_events() {
+            Some(events) => events,
+            None => return Response::error("503 Service Unavailable")
+        };
+        let now = vilnius_now();
+
+        match route {
+            Route::Week(monday) => {
+                let timetable = Timetable { events };
+                Response::json(events_to_json(&timetable.by_week(monday.iso_week())))
+            },
+            Route::Today => Response::json(events_to_json(events.iter().filter(|e| e.overlaps_date(now.date())))),
+            Route::Next => match events.iter().find(|e| e.start >= now) {
+                Some(event) => Response::json(event_to_json(event)),
+                None => Response::json(Value::Null)
+            },
+            Route::Events(module) => {
+                let module = module.map(|module| module.to_lowercase());
+                let matches = |event: &&Event| match &module {
+                    Some(module) => {
+                        event.module_label().to_lowercase().contains(module.as_str())
+                            || event.module_code.as_ref().map(|code| code.to_lowercase()).as_ref() == Some(module)
+                    },
+                    None => true
+                };
+                Response::json(events_to_json(events.iter().filter(matches)))
+            },
+            Route::TimetableJson => match (Timetable { events }).to_json() {
+                Ok(body) => Response {
+                    status: "200 OK",
+                    content_type: "application/json; charset=utf-8",
+                    body
+                },
+                Err(_) => Response::error("500 Internal Server Error")
+            },
+            Route::Calendar => {
+                let mut events = events;
+                events.extend(self.user_data.events.iter().map(|event| event.to_event()));
+                Response {
+                    status: "200 OK",
+                    content_type: "text/calendar; charset=utf-8",
+                    body: events_to_ics(&events)
+                }
+            }
+        }
+    }
+
+    fn handle_connection(&mut self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // Headers are not needed, but they need to be read before responding
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                break;
+            }
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let response = match (parts.next(), parts.next()) {
+            (Some(method), Some(target)) => self.handle(method, target),
+            _ => Response::error("400 Bad Request")
+        };
+        response.write_to(&mut stream, self.allow_cors)
+    }
+}
+
+/// Serves the timetable over HTTP instead of opening a window. Requests are handled one at a time.
+/// With `allow_cors` any website open in the browser can read the timetable, so it's off by default.
+/// What happens while serving, like failed requests, is passed to `log`.
+pub fn run_server(env: Environment, address: &str, allow_cors: bool, log: fn(&str)) -> io::Result<()> {
+    let loaded = env.config_store.load()
+        .map_err(|e| io::Error::new(io::ErrorKind::NotFound, format!("Failed to load config: {}", e)))?;
+    if let Some(message) = loaded.dropped_message() {
+        log(&format!("Some settings couldn't be read and were left out:\n{}", message));
+    }
+    let config = loaded.config;
+    let profile = config.profile()
+        .filter(|profile| profile.vidko.is_some())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Vidko code is not set, run the app once to set it"))?;
+    let vidkos = profile.vidkos();
+    let user_data = env.user_data_store.load(&profile.name).unwrap_or_default();
+
+    let mut server = Server {
+        cache: TimetableCache::new(env.timetable_getter, vidkos),
+        allow_cors,
+        config,
+        user_data,
+        log
+    };
+
+    let listener = TcpListener::bind(address)?;
+    log(&format!("Serving timetable on http://{}", address));
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| server.handle_connection(stream));
+        if let Err(e) = result {
+            log(&format!("Failed to handle request: {}", e));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, atomic::{AtomicBool, AtomicU32, Ordering}};
+
+    use crate::{config::Profile, test_utils::at, timetable::EventCategory};
+
+    use super::*;
+
+    // Counts its calls, and fails while `fail` is set
+    #[derive(Clone, Default)]
+    struct TestGetter {
+        calls: Arc<AtomicU32>,
+        fail: Arc<AtomicBool>
+    }
+
+    impl TimetableGetter for TestGetter {
+        fn get(&self, _vidko: &str) -> Result<Timetable, GetTimetableError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.fail.load(Ordering::SeqCst) {
+                return Err(GetTimetableError::RequestFailed("offline".into()));
+            }
+            Ok(Timetable {
+                events: vec![
+                    event("P175B123 Databases", "Databases", "P175B123", at(2023, 2, 6, 8, 30)),
+                    event("P150B001 Physics", "Physics", "P150B001", at(2023, 2, 7, 10, 0)),
+                    event("P175B123 Databases", "Databases", "P175B123", at(2023, 2, 13, 8, 30))
+                ]
+            })
+        }
+    }
+
+    fn event(summary: &str, module_name: &str, module_code: &str, start: chrono::NaiveDateTime) -> Event {
+        Event {
+            category: EventCategory::Yellow,
+            start,
+            end: start + chrono::Duration::minutes(90),
+            description: "Lecture".to_string(),
+            summary: summary.to_string(),
+            location: "XI r.-101".to_string(),
+            module_name: Some(module_name.to_string()),
+            module_code: Some(module_code.to_string()),
+            note: None,
+            attendance: None,
+            personal_id: None
+        }
+    }
+
+    fn server(getter: &TestGetter, config: Config) -> Server {
+        Server {
+            cache: TimetableCache::new(Box::new(getter.clone()), vec!["E1810".to_string()]),
+            allow_cors: false,
+            config,
+            user_data: UserData::default(),
+            log: |_| {}
+        }
+    }
+
+    fn json_body(response: &Response) -> Value {
+        assert_eq!(response.status, "200 OK");
+        serde_json::from_str(&response.body).unwrap()
+    }
+
+    fn summaries(value: &Value) -> Vec<&str> {
+        value.as_array().unwrap().iter().map(|event| event["summary"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn unknown_paths_do_not_fetch() {
+        let getter = TestGetter::default();
+        let mut server = server(&getter, Config::default());
+
+        assert_eq!(server.handle("GET", "/missing").status, "404 Not Found");
+        assert_eq!(server.handle("GET", "/week/nonsense").status, "400 Bad Request");
+        assert_eq!(server.handle("POST", "/today").status, "405 Method Not Allowed");
+        assert_eq!(getter.calls.load(Ordering::SeqCst), 0);
+
+        assert_eq!(server.handle("GET", "/today").status, "200 OK");
+        assert_eq!(getter.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn serves_weeks_and_modules() {
+        let getter = TestGetter::default();
+        let mut server = server(&getter, Config::default());
+
+        let week = json_body(&server.handle("GET", "/week/2023-W06"));
+        assert_eq!(summaries(&week), vec!["P175B123 Databases", "P150B001 Physics"]);
+        assert_eq!(week[0]["start"], "2023-02-06T08:30:00");
+        assert_eq!(week[0]["end"], "2023-02-06T10:00:00");
+        assert_eq!(week[0]["module_code"], "P175B123");
+        assert_eq!(week[0]["location"], "XI r.-101");
+        assert_eq!(week[0]["category"], "Yellow");
+        assert_eq!(week[0]["note"], Value::Null);
+
+        let by_name = json_body(&server.handle("GET", "/events?module=DATA%62ases"));
+        assert_eq!(summaries(&by_name), vec!["P175B123 Databases", "P175B123 Databases"]);
+        let by_code = json_body(&server.handle("GET", "/events?module=p150b001"));
+        assert_eq!(summaries(&by_code), vec!["P150B001 Physics"]);
+        assert_eq!(summaries(&json_body(&server.handle("GET", "/events"))).len(), 3);
+
+        let calendar = server.handle("GET", "/calendar.ics");
+        assert_eq!(calendar.content_type, "text/calendar; charset=utf-8");
+        assert_eq!(calendar.body.matches("BEGIN:VEVENT").count(), 3);
+
+        let timetable = server.handle("GET", "/timetable.json");
+        assert_eq!(Timetable::from_json(&timetable.body).unwrap().events.len(), 3);
+        assert_eq!(getter.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn leaves_out_hidden_modules() {
+        let mut config = Config::default();
+        let mut profile = Profile::new("Ana");
+        profile.hidden_modules = vec!["Physics".to_string()];
+        config.active_profile = profile.name.clone();
+        config.profiles = vec![profile];
+        let mut server = server(&TestGetter::default(), config);
+
+        let week = json_body(&server.handle("GET", "/week/2023-W06"));
+        assert_eq!(summaries(&week), vec!["P175B123 Databases"]);
+    }
+
+    #[test]
+    fn retries_failed_fetches_sooner() {
+        let getter = TestGetter::default();
+        let mut cache = TimetableCache::new(Box::new(getter.clone()), vec!["E1810".to_string()]);
+        let start = Instant::now();
+
+        getter.fail.store(true, Ordering::SeqCst);
+        assert!(cache.refresh(start).is_err());
+        assert!(cache.timetable.is_none());
+        assert!(cache.refresh(start + RETRY_INTERVAL / 2).is_ok());
+        assert_eq!(getter.calls.load(Ordering::SeqCst), 1);
+
+        getter.fail.store(false, Ordering::SeqCst);
+        assert!(cache.refresh(start + RETRY_INTERVAL).is_ok());
+        assert!(cache.timetable.is_some());
+        assert_eq!(getter.calls.load(Ordering::SeqCst), 2);
+
+        // A failed refresh keeps the old timetable
+        getter.fail.store(true, Ordering::SeqCst);
+        assert!(cache.refresh(start + RETRY_INTERVAL * 2).is_ok());
+        assert!(cache.refresh(start + RETRY_INTERVAL + REFRESH_INTERVAL).is_err());
+        assert!(cache.timetable.is_some());
+        assert_eq!(getter.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn decodes_percent_escapes() {
+        assert_eq!(percent_decode("data%20bases+2"), "data bases 2");
+        assert_eq!(percent_decode("%C5%A1ilumos"), "šilumos");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+        assert_eq!(query_param("a=1&module=x%26y", "module").as_deref(), Some("x&y"));
+        assert_eq!(query_param("modules=1", "module"), None);
+    }
+
+    #[test]
+    fn parses_iso_weeks() {
+        assert_eq!(parse_iso_week("2023-W05"), NaiveDate::from_ymd_opt(2023, 1, 30));
+        assert_eq!(parse_iso_week("2020-W53"), NaiveDate::from_ymd_opt(2020, 12, 28));
+        assert_eq!(parse_iso_week("2025-W01"), NaiveDate::from_ymd_opt(2024, 12, 30));
+        assert_eq!(parse_iso_week("2023-W53"), None);
+        assert_eq!(parse_iso_week("2023-W00"), None);
+        assert_eq!(parse_iso_week("2023-05"), None);
+        assert_eq!(parse_iso_week("W05"), None);
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+
+use egui::{Key, InputState};
+use ktu_timetable::keybindings::{Action, Keybindings};
+
+// Keybindings are stored as text in the library, this turns them into egui keys
+pub fn is_triggered(keybindings: &Keybindings, input: &InputState, action: Action) -> bool {
+    match Shortcut::parse(keybindings.get(action)) {
+        Some(shortcut) => shortcut.pressed(input),
+        None => false
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shortcut {
+    pub key: Key,
+    pub command: bool,
+    pub shift: bool,
+    pub alt: bool
+}
+
+const KEY_NAMES: [(Key, &str); 63] = [
+    (Key::ArrowDown, "Down"), (Key::ArrowLeft, "Left"), (Key::ArrowRight, "Right"), (Key::ArrowUp, "Up"),
+    (Key::Escape, "Escape"), (Key::Tab, "Tab"), (Key::Backspace, "Backspace"), (Key::Enter, "Enter"),
+    (Key::Space, "Space"), (Key::Insert, "Insert"), (Key::Delete, "Delete"), (Key::Home, "Home"),
+    (Key::End, "End"), (Key::PageUp, "PageUp"), (Key::PageDown, "PageDown"),
+    (Key::Num0, "0"), (Key::Num1, "1"), (Key::Num2, "2"), (Key::Num3, "3"), (Key::Num4, "4"),
+    (Key::Num5, "5"), (Key::Num6, "6"), (Key::Num7, "7"), (Key::Num8, "8"), (Key::Num9, "9"),
+    (Key::A, "A"), (Key::B, "B"), (Key::C, "C"), (Key::D, "D"), (Key::E, "E"), (Key::F, "F"),
+    (Key::G, "G"), (Key::H, "H"), (Key::I, "I"), (Key::J, "J"), (Key::K, "K"), (Key::L, "L"),
+    (Key::M, "M"), (Key::N, "N"), (Key::O, "O"), (Key::P, "P"), (Key::Q, "Q"), (Key::R, "R"),
+    (Key::S, "S"), (Key::T, "T"), (Key::U, "U"), (Key::V, "V"), (Key::W, "W"), (Key::X, "X"),
+    (Key::Y, "Y"), (Key::Z, "Z"),
+    (Key::F1, "F1"), (Key::F2, "F2"), (Key::F3, "F3"), (Key::F4, "F4"), (Key::F5, "F5"),
+    (Key::F6, "F6"), (Key::F7, "F7"), (Key::F8, "F8"), (Key::F9, "F9"), (Key::F10, "F10"),
+    (Key::F11, "F11"), (Key::F12, "F12")
+];
+
+fn key_name(key: Key) -> Option<&'static str> {
+    KEY_NAMES.iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, name)| *name)
+}
+
+fn key_from_name(name: &str) -> Option<Key> {
+    KEY_NAMES.iter()
+        .find(|(_, n)| n.eq_ignore_ascii_case(name))
+        .map(|(key, _)| *key)
+}
+
+impl Shortcut {
+    pub fn new(key: Key, modifiers: egui::Modifiers) -> Self {
+        Self {
+            key,
+            command: modifiers.command,
+            shift: modifiers.shift,
+            alt: modifiers.alt
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut shortcut = Shortcut { key: Key::Escape, command: false, shift: false, alt: false };
+        let mut key = None;
+        for part in text.split('+').map(str::trim) {
+            match part.to_lowercase().as_str() {
+                "ctrl" | "cmd" => shortcut.command = true,
+                "shift" => shortcut.shift = true,
+                "alt" => shortcut.alt = true,
+                _ => key = Some(key_from_name(part)?)
+            }
+        }
+        shortcut.key = key?;
+        Some(shortcut)
+    }
+
+    pub fn pressed(&self, input: &InputState) -> bool {
+        input.key_pressed(self.key)
+            && input.modifiers.command == self.command
+            && input.modifiers.shift == self.shift
+            && input.modifiers.alt == self.alt
+    }
+}
+
+impl fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.command {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        write!(f, "{}", key_name(self.key).unwrap_or("?"))
+    }
+}
@@ -0,0 +1,187 @@
+use std::{cmp::Reverse, collections::BTreeMap};
+
+use chrono::{Datelike, IsoWeek, NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::{timetable::{Event, EventCategory}, utils::week_monday};
+
+// How many of the busiest dates are listed
+const BUSIEST_DATES_COUNT: usize = 5;
+
+/// Contact hours of one week, in minutes
+#[derive(Debug, Clone)]
+pub struct WeekLoad {
+    pub week: IsoWeek,
+    pub minutes: i64,
+    /// Start of the earliest and end of the latest class that week
+    pub earliest: Option<NaiveTime>,
+    pub latest: Option<NaiveTime>
+}
+
+/// Contact hours of the official timetable, all durations are in minutes
+#[derive(Debug, Clone, Default)]
+pub struct Statistics {
+    pub total_minutes: i64,
+    pub remaining_minutes: i64,
+    /// Sorted from the most to the least time consuming
+    pub per_module: Vec<(String, i64)>,
+    pub per_kind: Vec<(EventCategory, i64)>,
+    /// Indexed by the number of days from monday
+    pub per_weekday: [i64; 7],
+    pub busiest_dates: Vec<(NaiveDate, i64)>,
+    pub weeks: Vec<WeekLoad>
+}
+
+fn sorted_by_minutes<K>(map: impl IntoIterator<Item = (K, i64)>) -> Vec<(K, i64)> {
+    let mut entries: Vec<(K, i64)> = map.into_iter().collect();
+    entries.sort_by_key(|entry| Reverse(entry.1));
+    entries
+}
+
+impl Statistics {
+    /// Personal events are not counted, `now` is used to determine what is still remaining
+    pub fn new<'a>(events: impl IntoIterator<Item = &'a Event>, now: NaiveDateTime) -> Self {
+        let mut stats = Statistics::default();
+        let mut per_module: BTreeMap<&str, i64> = BTreeMap::new();
+        let mut per_kind: Vec<(EventCategory, i64)> = vec![];
+        let mut per_date: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+        // Keyed by mondays, as `IsoWeek`s of the same week can differ, see `same_week`
+        let mut weeks: BTreeMap<NaiveDate, WeekLoad> = BTreeMap::new();
+
+        for event in events {
+            if event.personal_id.is_some() {
+                continue;
+            }
+
+            let minutes = (event.end - event.start).num_minutes();
+            stats.total_minutes += minutes;
+            if event.end > now {
+                stats.remaining_minutes += (event.end - event.start.max(now)).num_minutes();
+            }
+            *per_module.entry(event.module_label()).or_insert(0) += minutes;
+            match per_kind.iter_mut().find(|(kind, _)| *kind == event.category) {
+                Some((_, kind_minutes)) => *kind_minutes += minutes,
+                None => per_kind.push((event.category, minutes))
+            }
+
+            // Events spanning over midnight are split between their days
+            for date in event.dates() {
+                if let Some((start, end)) = event.clipped_to(date) {
+                    let minutes = (end - start).num_minutes();
+                    *per_date.entry(date).or_insert(0) += minutes;
+                    stats.per_weekday[date.weekday().num_days_from_monday() as usize] += minutes;
+
+                    let monday = week_monday(date.iso_week());
+                    let week = weeks.entry(monday).or_insert(WeekLoad {
+                        week: monday.iso_week(),
+                        minutes: 0,
+                        earliest: None,
+                        latest: None
+                    });
+                    week.minutes += minutes;
+                    week.earliest = Some(week.earliest.map_or(start.time(), |time| time.min(start.time())));
+                    if end.date() == date {
+                        week.latest = Some(week.latest.map_or(end.time(), |time| time.max(end.time())));
+                    }
+                }
+            }
+        }
+
+        stats.per_module = sorted_by_minutes(per_module.into_iter().map(|(module, minutes)| (module.to_string(), minutes)));
+        stats.per_kind = sorted_by_minutes(per_kind);
+        stats.busiest_dates = sorted_by_minutes(per_date);
+        stats.busiest_dates.truncate(BUSIEST_DATES_COUNT);
+        stats.weeks = weeks.into_values().collect();
+        stats
+    }
+
+    /// Minutes of the busiest week, 0 when there are no weeks
+    pub fn max_week_minutes(&self) -> i64 {
+        self.weeks.iter().map(|week| week.minutes).max().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::at;
+
+    use super::*;
+
+    fn event(summary: &str, start: NaiveDateTime, end: NaiveDateTime) -> Event {
+        Event {
+            category: EventCategory::Yellow,
+            start,
+            end,
+            description: String::new(),
+            summary: summary.to_string(),
+            location: String::new(),
+            module_name: None,
+            module_code: None,
+            note: None,
+            attendance: None,
+            personal_id: None
+        }
+    }
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn week_spanning_new_year_is_one_row() {
+        // 2024-12-30 and 2025-01-03 are both in 2025-W01
+        let events = [
+            event("Databases", at(2024, 12, 30, 8, 30), at(2024, 12, 30, 10, 0)),
+            event("Physics", at(2025, 1, 3, 14, 0), at(2025, 1, 3, 15, 30))
+        ];
+        let stats = Statistics::new(&events, at(2024, 12, 1, 0, 0));
+
+        assert_eq!(stats.weeks.len(), 1);
+        let week = &stats.weeks[0];
+        assert_eq!((week.week.year(), week.week.week()), (2025, 1));
+        assert_eq!(week.minutes, 180);
+        assert_eq!(week.earliest, Some(time(8, 30)));
+        assert_eq!(week.latest, Some(time(15, 30)));
+    }
+
+    #[test]
+    fn sums_modules_and_what_remains() {
+        let mut personal = event("Study", at(2023, 2, 6, 12, 0), at(2023, 2, 6, 20, 0));
+        personal.personal_id = Some(1);
+        let events = [
+            event("Databases", at(2023, 2, 6, 8, 30), at(2023, 2, 6, 10, 0)),
+            event("Physics", at(2023, 2, 7, 10, 0), at(2023, 2, 7, 11, 0)),
+            event("Databases", at(2023, 2, 8, 8, 30), at(2023, 2, 8, 10, 0)),
+            personal
+        ];
+        let stats = Statistics::new(&events, at(2023, 2, 7, 10, 30));
+
+        assert_eq!(stats.total_minutes, 240);
+        assert_eq!(stats.per_module, vec![("Databases".to_string(), 180), ("Physics".to_string(), 60)]);
+        // Half of the physics class and the whole last lecture
+        assert_eq!(stats.remaining_minutes, 120);
+        assert_eq!(stats.per_kind, vec![(EventCategory::Yellow, 240)]);
+    }
+
+    #[test]
+    fn splits_events_at_midnight() {
+        // From a sunday evening into the monday of the next week
+        let events = [event("Exam", at(2023, 2, 5, 22, 0), at(2023, 2, 6, 1, 30))];
+        let stats = Statistics::new(&events, at(2023, 1, 1, 0, 0));
+
+        assert_eq!(stats.total_minutes, 210);
+        assert_eq!(stats.per_weekday[6], 120);
+        assert_eq!(stats.per_weekday[0], 90);
+        assert_eq!(stats.busiest_dates, vec![
+            (NaiveDate::from_ymd_opt(2023, 2, 5).unwrap(), 120),
+            (NaiveDate::from_ymd_opt(2023, 2, 6).unwrap(), 90)
+        ]);
+
+        let weeks: Vec<(u32, i64)> = stats.weeks.iter().map(|week| (week.week.week(), week.minutes)).collect();
+        assert_eq!(weeks, vec![(5, 120), (6, 90)]);
+        // The part before midnight doesn't end that day
+        assert_eq!(stats.weeks[0].earliest, Some(time(22, 0)));
+        assert_eq!(stats.weeks[0].latest, None);
+        assert_eq!(stats.weeks[1].earliest, Some(time(0, 0)));
+        assert_eq!(stats.weeks[1].latest, Some(time(1, 30)));
+    }
+}
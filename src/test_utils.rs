@@ -0,0 +1,32 @@
+// Helpers shared by the tests of several modules
+
+use std::{fs, path::{Path, PathBuf}, process};
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+pub fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+}
+
+// An empty directory which is removed when dropped.
+// Every test needs its own `name`, as they run in parallel.
+pub struct TempDir {
+    path: PathBuf
+}
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("ktu-timetable-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
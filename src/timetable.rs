@@ -1,54 +1,276 @@
 use ical::property::Property;
 use std::{error::Error, fmt};
-use std::io::BufReader;
-use chrono::{NaiveDate, NaiveTime, IsoWeek, Datelike};
-use lazy_regex::{regex_captures};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::{fs, path::{Path, PathBuf}, sync::{Arc, Mutex}, thread, time};
+use chrono::{NaiveDate, NaiveTime, NaiveDateTime, IsoWeek, Duration};
+use lazy_regex::{regex_captures, regex_is_match};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+use crate::{recurrence::{RecurrenceRule, parse_ical_value, parse_ical_datetime, parse_duration, to_vilnius}, user_data::Attendance, utils::week_monday, config::{HttpConfig, HttpConfigError, write_atomic}};
+
+/// Colour of an event, KTU uses it for the kind of class.
+///
+/// The serialized forms of `EventCategory`, `Event` and `Timetable` are stored in files,
+/// changing them requires increasing `serialization::FORMAT_VERSION`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EventCategory {
     Default,
     Yellow,
     Green,
     Red,
-    Blue
+    Blue,
+    /// Events added by the user, not from the official timetable
+    Personal
 }
 
-#[derive(Debug, Clone)]
+/// All dates and times are in Europe/Vilnius time.
+/// An event can span over multiple days, `end` is exclusive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Event {
     pub category: EventCategory,
-    pub date: NaiveDate,
-    pub start_time: NaiveTime,
-    pub end_time: NaiveTime,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
     pub description: String,
     pub summary: String,
     pub location: String,
 
-    pub module_name: Option<String>
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module_code: Option<String>,
+
+    /// User's own note attached to the event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attendance: Option<Attendance>,
+    /// Id of the personal event this was created from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub personal_id: Option<u64>
 }
 
+/// Not `Serialize`/`Deserialize` on purpose, it's only stored through `to_json` and `to_toml`,
+/// which write `serialization::FORMAT_VERSION` alongside it
 #[derive(Debug, Clone)]
 pub struct Timetable {
     pub events: Vec<Event>
 }
 
+/// Time without events, found by `Timetable::free_slots`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FreeSlot {
+    pub date: NaiveDate,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime
+}
+
+/// Which part of events `Timetable::search` looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchField {
+    Any,
+    Module,
+    Code,
+    Room,
+    Lecturer,
+    Description
+}
+
+/// Why a timetable couldn't be downloaded or read
 #[derive(Debug)]
 pub enum GetTimetableError {
     NotFound,
-    EmptyTimetable
+    /// Network error or an error on the server, after all retries
+    RequestFailed(String),
+    EmptyTimetable,
+    InvalidDate(String),
+    /// A TZID other than UTC or Europe/Vilnius, which can't be converted
+    UnsupportedTimezone(String)
 }
 
 impl Timetable {
+    /// Events which happen at least partly in `week`
     pub fn by_week(&self, week: IsoWeek) -> Vec<Event> {
+        let monday = week_monday(week);
+        let from = day_start(monday);
+        let to = day_start(monday + Duration::days(7));
         return self.events.iter()
-            .filter(|e| e.date.iso_week() == week)
+            .filter(|e| e.overlaps(from, to))
             .map(|e| e.clone())
             .collect();
     }
+
+    /// All events of several timetables, sorted by their start
+    pub fn merge<'a>(timetables: impl IntoIterator<Item = &'a Timetable>) -> Timetable {
+        let mut events: Vec<Event> = timetables.into_iter()
+            .flat_map(|t| t.events.iter().cloned())
+            .collect();
+        events.sort_by_key(|event| event.start);
+        Timetable { events }
+    }
+
+    /// Every location of the timetable once, sorted
+    pub fn rooms(&self) -> Vec<String> {
+        let mut rooms: Vec<String> = self.events.iter()
+            .map(|e| e.location.clone())
+            .filter(|location| !location.is_empty())
+            .collect();
+        rooms.sort();
+        rooms.dedup();
+        rooms
+    }
+
+    /// Gaps of at least `min_length` between events from `from` to `to` (inclusive),
+    /// only looking at the time between `day_start` and `day_end`
+    pub fn free_slots(&self, from: NaiveDate, to: NaiveDate, day_start: NaiveTime, day_end: NaiveTime, min_length: Duration) -> Vec<FreeSlot> {
+        self.free_slots_where(from, to, day_start, day_end, min_length, |_| true)
+    }
+
+    /// Same as `free_slots`, but only events happening in `room` make it busy
+    pub fn free_slots_in_room(&self, room: &str, from: NaiveDate, to: NaiveDate, day_start: NaiveTime, day_end: NaiveTime, min_length: Duration) -> Vec<FreeSlot> {
+        self.free_slots_where(from, to, day_start, day_end, min_length, |e| e.location == room)
+    }
+
+    fn free_slots_where<P>(&self, from: NaiveDate, to: NaiveDate, day_start: NaiveTime, day_end: NaiveTime, min_length: Duration, is_busy: P) -> Vec<FreeSlot>
+        where P: Fn(&Event) -> bool
+    {
+        let mut slots = vec![];
+        let mut date = from;
+        while date <= to {
+            let window_start = date.and_time(day_start);
+            let window_end = date.and_time(day_end);
+            let mut busy: Vec<(NaiveDateTime, NaiveDateTime)> = self.events.iter()
+                .filter(|e| e.overlaps(window_start, window_end) && is_busy(*e))
+                .map(|e| (e.start.max(window_start), e.end.min(window_end)))
+                .collect();
+            busy.sort();
+
+            let mut cursor = window_start;
+            for (start, end) in busy {
+                if start - cursor >= min_length && start > cursor {
+                    slots.push(FreeSlot { date, start_time: cursor.time(), end_time: start.time() });
+                }
+                cursor = cursor.max(end);
+            }
+            if window_end - cursor >= min_length && window_end > cursor {
+                slots.push(FreeSlot { date, start_time: cursor.time(), end_time: day_end });
+            }
+
+            date += Duration::days(1);
+        }
+        slots
+    }
+
+    /// `Event::module_label` of every module once, sorted
+    pub fn module_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.events.iter()
+            .map(|e| e.module_label().to_string())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Date of the earliest event, None when there are no events
+    pub fn first_date(&self) -> Option<NaiveDate> {
+        self.events.iter().map(|e| e.start.date()).min()
+    }
+
+    /// Last date any event happens on
+    pub fn last_date(&self) -> Option<NaiveDate> {
+        self.events.iter().filter_map(|e| e.dates().last().copied()).max()
+    }
+
+    /// Latest time of day an event ends at
     pub fn max_end_time(&self) -> Option<NaiveTime> {
         return self.events.iter()
-            .map(|e| e.end_time)
+            .map(|e| e.end.time())
             .max();
     }
+
+    /// Case-insensitive substring search over all events of the semester
+    pub fn search(&self, query: &str, field: SearchField) -> Vec<Event> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return vec![];
+        }
+
+        return self.events.iter()
+            .filter(|e| e.matches(&query, field))
+            .map(|e| e.clone())
+            .collect();
+    }
+}
+
+fn day_start(date: NaiveDate) -> NaiveDateTime {
+    date.and_hms_opt(0, 0, 0).unwrap()
+}
+
+impl Event {
+    /// Whether any part of the event happens between `from` (inclusive) and `to` (exclusive)
+    pub fn overlaps(&self, from: NaiveDateTime, to: NaiveDateTime) -> bool {
+        self.start < to && (self.end > from || self.start >= from)
+    }
+
+    /// Whether any part of the event happens on `date`
+    pub fn overlaps_date(&self, date: NaiveDate) -> bool {
+        self.overlaps(day_start(date), day_start(date + Duration::days(1)))
+    }
+
+    /// The part of the event which happens on `date`
+    pub fn clipped_to(&self, date: NaiveDate) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        if !self.overlaps_date(date) {
+            return None;
+        }
+        Some((self.start.max(day_start(date)), self.end.min(day_start(date + Duration::days(1)))))
+    }
+
+    /// All days on which the event happens
+    pub fn dates(&self) -> Vec<NaiveDate> {
+        let mut dates = vec![];
+        let mut date = self.start.date();
+        while self.overlaps_date(date) {
+            dates.push(date);
+            date += Duration::days(1);
+        }
+        dates
+    }
+
+    /// Start and end time, like "08:30 - 10:00", with dates when the event spans several days
+    pub fn time_label(&self) -> String {
+        if self.start.date() == self.end.date() || self.end == day_start(self.start.date() + Duration::days(1)) {
+            format!("{} - {}", self.start.format("%H:%M"), self.end.format("%H:%M"))
+        } else {
+            format!("{} - {}", self.start.format("%m-%d %H:%M"), self.end.format("%m-%d %H:%M"))
+        }
+    }
+
+    /// Identifies an occurrence of an event across timetable refreshes, used to attach notes.
+    /// Parallel groups of a module meet at the same time with the same summary, only the room differs.
+    pub fn key(&self) -> String {
+        format!("{} {} {}", self.start.format("%Y-%m-%dT%H:%M"), self.summary, self.location)
+    }
+
+    /// Name of the module, or the whole summary if it couldn't be determined
+    pub fn module_label(&self) -> &str {
+        self.module_name.as_ref().unwrap_or(&self.summary)
+    }
+
+    fn matches(&self, query: &str, field: SearchField) -> bool {
+        let contains = |text: &str| text.to_lowercase().contains(query);
+        let contains_opt = |text: &Option<String>| text.as_deref().map_or(false, contains);
+
+        match field {
+            SearchField::Any => {
+                contains(&self.summary) || contains(&self.location) || contains(&self.description)
+            },
+            SearchField::Module => contains_opt(&self.module_name) || contains(&self.summary),
+            SearchField::Code => contains_opt(&self.module_code),
+            SearchField::Room => contains(&self.location),
+            // KTU puts the lecturer's name into the description of an event
+            SearchField::Lecturer | SearchField::Description => contains(&self.description),
+        }
+    }
 }
 
 impl Error for GetTimetableError {}
@@ -58,7 +280,10 @@ impl fmt::Display for GetTimetableError {
         use GetTimetableError::*;
         match self {
             NotFound => write!(f, "Timetable not found"),
-            EmptyTimetable => write!(f, "Timetable is empty")
+            RequestFailed(e) => write!(f, "Request failed: {}", e),
+            EmptyTimetable => write!(f, "Timetable is empty"),
+            InvalidDate(value) => write!(f, "Invalid date '{}'", value),
+            UnsupportedTimezone(tzid) => write!(f, "Unsupported timezone '{}'", tzid)
         }
     }
 }
@@ -71,84 +296,489 @@ fn guess_module_name(summary: &str) -> Option<String> {
     None
 }
 
-pub trait TimetableGetter {
+fn guess_module_code(summary: &str) -> Option<String> {
+    let captures = regex_captures!(r"^(\w\d{3}\w\d{3}) ", summary);
+    if let Some((_, module_code)) = captures {
+        return Some(module_code.into());
+    }
+    None
+}
+
+fn find_param<'a>(prop: &'a Property, name: &str) -> Option<&'a str> {
+    prop.params.as_ref()?
+        .iter()
+        .find(|(param_name, _)| param_name == name)
+        .and_then(|(_, values)| values.first())
+        .map(|value| value.as_str())
+}
+
+const UTC_TZIDS: [&str; 4] = ["UTC", "Etc/UTC", "GMT", "Etc/GMT"];
+// Names of Europe/Vilnius time, the second one is used by Outlook
+const VILNIUS_TZIDS: [&str; 2] = ["Europe/Vilnius", "FLE Standard Time"];
+
+// Whether the TZID of a property means UTC. Floating times and Vilnius times are taken as they are,
+// other timezones are an error, because there is no timezone database to convert them with.
+fn is_utc_param(prop: &Property) -> Result<bool, GetTimetableError> {
+    let tzid = match find_param(prop, "TZID") {
+        // A leading "/" marks a globally unique TZID
+        Some(tzid) => tzid.trim_matches('"').trim_start_matches('/'),
+        None => return Ok(false)
+    };
+    if UTC_TZIDS.contains(&tzid) {
+        return Ok(true);
+    }
+    if VILNIUS_TZIDS.contains(&tzid) {
+        return Ok(false);
+    }
+    Err(GetTimetableError::UnsupportedTimezone(tzid.to_string()))
+}
+
+// DATE or DATE-TIME of a property as it is written, and whether it is in UTC ("Z" suffix or TZID=UTC)
+fn parse_raw_datetime(prop: &Property) -> Result<(NaiveDateTime, bool), GetTimetableError> {
+    let value = prop.value.as_deref().unwrap_or("");
+    let is_utc = is_utc_param(prop)?;
+    let (datetime, is_z) = parse_ical_value(value)
+        .ok_or_else(|| GetTimetableError::InvalidDate(value.to_string()))?;
+    // DATE values have no time of day to convert
+    Ok((datetime, value.contains('T') && (is_utc || is_z)))
+}
+
+// Converts a DATE or DATE-TIME property into Vilnius time.
+// UTC times are converted, floating and Vilnius times are taken as they are.
+fn parse_datetime(prop: &Property) -> Result<NaiveDateTime, GetTimetableError> {
+    let (datetime, is_utc) = parse_raw_datetime(prop)?;
+    Ok(to_vilnius(datetime, is_utc))
+}
+
+// EXDATE and RDATE properties can hold several comma separated values
+fn parse_datetime_list(prop: &Property) -> Result<Vec<NaiveDateTime>, GetTimetableError> {
+    // Periods ("start/end") are not supported in RDATE
+    if find_param(prop, "VALUE") == Some("PERIOD") {
+        return Ok(vec![]);
+    }
+
+    let is_utc = is_utc_param(prop)?;
+    let mut datetimes = vec![];
+    for value in prop.value.as_deref().unwrap_or("").split(',').filter(|value| !value.is_empty()) {
+        let datetime = parse_ical_datetime(value, is_utc)
+            .ok_or_else(|| GetTimetableError::InvalidDate(value.to_string()))?;
+        datetimes.push(datetime);
+    }
+    Ok(datetimes)
+}
+
+fn find_property<'a>(properties: &'a [Property], name: &str) -> Option<&'a Property> {
+    properties.iter().find(|prop| prop.name == name)
+}
+
+fn property_value(properties: &[Property], name: &str) -> String {
+    find_property(properties, name)
+        .and_then(|prop| prop.value.clone())
+        .unwrap_or_default()
+}
+
+// Occurrence start times of a single VEVENT, with RRULE, RDATE and EXDATE applied
+fn event_occurrences(properties: &[Property], start_prop: &Property, overridden: &[NaiveDateTime]) -> Result<Vec<NaiveDateTime>, GetTimetableError> {
+    let (start, is_utc) = parse_raw_datetime(start_prop)?;
+    let mut occurrences = vec![to_vilnius(start, is_utc)];
+    if let Some(rule) = find_property(properties, "RRULE").and_then(|prop| prop.value.as_deref()) {
+        // Rules which can't be understood are ignored, showing at least the first occurrence
+        if let Some(rule) = RecurrenceRule::parse(rule) {
+            // UTC events repeat at the same UTC time, so in Vilnius time they move with daylight saving
+            occurrences = rule.occurrences(start, is_utc)
+                .into_iter()
+                .map(|occurrence| to_vilnius(occurrence, is_utc))
+                .collect();
+        }
+    }
+
+    for prop in properties.iter().filter(|prop| prop.name == "RDATE") {
+        occurrences.extend(parse_datetime_list(prop)?);
+    }
+
+    let mut excluded = overridden.to_vec();
+    for prop in properties.iter().filter(|prop| prop.name == "EXDATE") {
+        // A DATE exception excludes every occurrence on that day
+        let is_date = prop.value.as_deref().map_or(false, |value| !value.contains('T'));
+        for datetime in parse_datetime_list(prop)? {
+            if is_date {
+                excluded.extend(occurrences.iter().filter(|occurrence| occurrence.date() == datetime.date()));
+            } else {
+                excluded.push(datetime);
+            }
+        }
+    }
+
+    occurrences.retain(|occurrence| !excluded.contains(occurrence));
+    occurrences.sort();
+    occurrences.dedup();
+    Ok(occurrences)
+}
+
+/// Parses a whole iCalendar file, expanding recurring events into separate events
+pub fn parse_timetable<R: BufRead>(reader: R) -> Result<Timetable, GetTimetableError> {
+    let mut reader = ical::IcalParser::new(reader);
+    let cal = match reader.next() {
+        Some(Ok(cal)) => cal,
+        _ => return Err(GetTimetableError::NotFound)
+    };
+    if cal.events.is_empty() {
+        return Err(GetTimetableError::EmptyTimetable);
+    }
+
+    // Modified occurrences are separate VEVENTs with the same UID and a RECURRENCE-ID,
+    // the original occurrence needs to be removed from the recurring event
+    let mut overrides: HashMap<String, Vec<NaiveDateTime>> = HashMap::new();
+    for event in &cal.events {
+        if let Some(recurrence_id) = find_property(&event.properties, "RECURRENCE-ID") {
+            let uid = property_value(&event.properties, "UID");
+            overrides.entry(uid).or_default().push(parse_datetime(recurrence_id)?);
+        }
+    }
+
+    let mut timetable = Timetable { events: vec![] };
+    for event in cal.events {
+        let properties = &event.properties;
+        let start_prop = find_property(properties, "DTSTART")
+            .ok_or_else(|| GetTimetableError::InvalidDate(String::new()))?;
+        let start = parse_datetime(start_prop)?;
+        let is_date = start_prop.value.as_deref().map_or(false, |value| !value.contains('T'));
+        let duration = find_property(properties, "DURATION").and_then(|prop| prop.value.as_deref());
+        let end = match (find_property(properties, "DTEND"), duration) {
+            (Some(end_prop), _) => parse_datetime(end_prop)?,
+            (None, Some(duration)) => parse_duration(duration)
+                .and_then(|duration| start.checked_add_signed(duration))
+                .ok_or_else(|| GetTimetableError::InvalidDate(duration.to_string()))?,
+            // Events without an end are either a whole day or have no length
+            (None, None) if is_date => start.checked_add_signed(Duration::days(1)).unwrap_or(start),
+            (None, None) => start
+        };
+
+        let category = match property_value(properties, "CATEGORIES").as_str() {
+            "Yellow Category" => EventCategory::Yellow,
+            "Green Category" => EventCategory::Green,
+            "Red Category" => EventCategory::Red,
+            "Blue Category" => EventCategory::Blue,
+            _ => EventCategory::Default
+        };
+        let summary = property_value(properties, "SUMMARY");
+        let template = Event {
+            category,
+            start,
+            end,
+            description: property_value(properties, "DESCRIPTION"),
+            module_name: guess_module_name(&summary),
+            module_code: guess_module_code(&summary),
+            summary,
+            location: property_value(properties, "LOCATION"),
+            note: None,
+            attendance: None,
+            personal_id: None
+        };
+
+        let overridden = if find_property(properties, "RECURRENCE-ID").is_none() {
+            overrides.get(&property_value(properties, "UID")).cloned().unwrap_or_default()
+        } else {
+            vec![]
+        };
+        for occurrence in event_occurrences(properties, start_prop, &overridden)? {
+            timetable.events.push(Event {
+                start: occurrence,
+                end: occurrence.checked_add_signed(end - start).unwrap_or(occurrence),
+                ..template.clone()
+            });
+        }
+    }
+
+    timetable.events.sort_by_key(|event| event.start);
+
+    Ok(timetable)
+}
+
+/// Vidko codes are typed by hand, so surrounding spaces and lower case letters are allowed
+pub fn normalize_vidko(text: &str) -> String {
+    text.trim().to_uppercase()
+}
+
+/// Checks a normalized vidko code, like "E1810", before it's downloaded. KTU doesn't document the format,
+/// so only what can't be a code is rejected: anything other than up to 16 letters and digits.
+pub fn is_valid_vidko(vidko: &str) -> bool {
+    regex_is_match!(r"^[A-Z0-9]{1,16}$", vidko)
+}
+
+/// Source of timetables by their vidko code, shared with the threads which download them in the background
+pub trait TimetableGetter: Send + Sync {
+    /// Timetable of a single vidko code
     fn get(&self, vidko: &str) -> Result<Timetable, GetTimetableError>;
+
+    /// Downloads every timetable and merges them into one, failing if any of them fails
+    fn get_merged(&self, vidkos: &[String]) -> Result<Timetable, GetTimetableError> {
+        let mut timetables = vec![];
+        for vidko in vidkos {
+            timetables.push(self.get(vidko)?);
+        }
+        Ok(Timetable::merge(&timetables))
+    }
+
+    /// Last timetable `get` gave for `vidko`, for showing something while offline
+    fn cached(&self, _vidko: &str) -> Option<Timetable> {
+        None
+    }
+
+    /// Same as `get_merged` from `cached` timetables, None unless every one of them is cached
+    fn cached_merged(&self, vidkos: &[String]) -> Option<Timetable> {
+        let timetables = vidkos.iter()
+            .map(|vidko| self.cached(vidko))
+            .collect::<Option<Vec<Timetable>>>()?;
+        Some(Timetable::merge(&timetables))
+    }
+
+    /// Takes what went wrong without failing `get`, like the downloaded timetables not being kept for later
+    fn take_warning(&self) -> Option<String> {
+        None
+    }
+
+    /// Applies changed HTTP settings without a restart, getters which don't download anything ignore them
+    fn set_http_config(&self, _config: &HttpConfig) -> Result<(), HttpConfigError> {
+        Ok(())
+    }
+}
+
+// Longest wait between two retries, no matter how many there were or how large `retry_delay_ms` is
+const MAX_RETRY_DELAY: time::Duration = time::Duration::from_secs(30);
+
+// Errors which could go away by trying again a bit later
+fn is_transient(error: &ureq::Error) -> bool {
+    match error {
+        ureq::Error::Status(status, _) => *status == 429 || *status >= 500,
+        // Timeouts are I/O errors, or connection failures when connecting times out.
+        // Invalid URLs, unknown hosts and proxy errors fail the same way every time.
+        ureq::Error::Transport(transport) => matches!(transport.kind(), ureq::ErrorKind::Io | ureq::ErrorKind::ConnectionFailed)
+    }
+}
+
+// Values the server gave to ask whether a download has changed since
+#[derive(Clone, Default)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>
+}
+
+// Last downloaded timetable of a URL, without validators it's only used while offline
+struct CachedTimetable {
+    validators: Validators,
+    timetable: Timetable
+}
+
+// How the download cache is stored, so that "304 Not Modified" works after a restart too
+#[derive(Serialize, Deserialize)]
+struct StoredTimetable {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    // In the versioned format of `serialization`
+    timetable: serde_json::Value
+}
+
+// A missing or unreadable file gives an empty cache, it only means downloading everything again
+fn read_cache(filename: &Path) -> HashMap<String, CachedTimetable> {
+    let stored: Vec<StoredTimetable> = match fs::read_to_string(filename).ok().and_then(|text| serde_json::from_str(&text).ok()) {
+        Some(stored) => stored,
+        None => return HashMap::new()
+    };
+
+    stored.into_iter()
+        .filter_map(|entry| {
+            // Timetables stored by a newer version of the app are downloaded again
+            let timetable = Timetable::from_value(entry.timetable).ok()?;
+            let validators = Validators { etag: entry.etag, last_modified: entry.last_modified };
+            Some((entry.url, CachedTimetable { validators, timetable }))
+        })
+        .collect()
+}
+
+fn write_cache(filename: &Path, cache: &HashMap<String, CachedTimetable>) -> Result<(), Box<dyn Error>> {
+    let mut stored = vec![];
+    for (url, cached) in cache {
+        stored.push(StoredTimetable {
+            url: url.clone(),
+            etag: cached.validators.etag.clone(),
+            last_modified: cached.validators.last_modified.clone(),
+            timetable: cached.timetable.to_value()?
+        });
+    }
+
+    if let Some(directory) = filename.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    write_atomic(filename, serde_json::to_string(&stored)?.as_bytes())?;
+    Ok(())
+}
+
+// An agent built from `HttpConfig`, replaced as a whole when the settings change
+struct HttpClient {
+    agent: ureq::Agent,
+    config: HttpConfig
 }
 
-#[derive(Default)]
-pub struct BlockingTimetableGetter {}
+impl HttpClient {
+    fn new(config: HttpConfig) -> Result<Self, HttpConfigError> {
+        let mut builder = ureq::AgentBuilder::new()
+            .timeout(time::Duration::from_secs(config.timeout_secs))
+            .user_agent(&config.user_agent);
+
+        if let Some(proxy) = &config.proxy {
+            let proxy = ureq::Proxy::new(proxy)
+                .map_err(|e| HttpConfigError::InvalidProxy(e.to_string()))?;
+            builder = builder.proxy(proxy);
+        }
+
+        if let Some(filename) = &config.ca_certificate {
+            let pem = fs::read(filename)
+                .map_err(HttpConfigError::CertificateFileError)?;
+            let certificate = native_tls::Certificate::from_pem(&pem)
+                .map_err(HttpConfigError::CertificateError)?;
+            let connector = native_tls::TlsConnector::builder()
+                .add_root_certificate(certificate)
+                .build()
+                .map_err(HttpConfigError::CertificateError)?;
+            builder = builder.tls_connector(Arc::new(connector));
+        }
+
+        Ok(Self { agent: builder.build(), config })
+    }
+
+    // With validators of a cached timetable the server can answer with "304 Not Modified" instead
+    fn fetch(&self, url: &str, validators: &Validators) -> Result<ureq::Response, GetTimetableError> {
+        let mut delay = time::Duration::from_millis(self.config.retry_delay_ms).min(MAX_RETRY_DELAY);
+        let mut attempt = 0;
+        loop {
+            let mut request = self.agent.get(url);
+            for (name, value) in &self.config.headers {
+                request = request.set(name, value);
+            }
+            if let Some(etag) = &validators.etag {
+                request = request.set("If-None-Match", etag);
+            }
+            if let Some(last_modified) = &validators.last_modified {
+                request = request.set("If-Modified-Since", last_modified);
+            }
+
+            match request.call() {
+                Ok(resp) => return Ok(resp),
+                Err(e) if is_transient(&e) => {
+                    if attempt >= self.config.retries {
+                        return Err(GetTimetableError::RequestFailed(e.to_string()));
+                    }
+                    thread::sleep(delay);
+                    delay = delay.saturating_mul(2).min(MAX_RETRY_DELAY);
+                    attempt += 1;
+                },
+                // Other client errors mean that there is no timetable for this vidko code
+                Err(ureq::Error::Status(..)) => return Err(GetTimetableError::NotFound),
+                Err(e) => return Err(GetTimetableError::RequestFailed(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Downloads timetables with the `HttpConfig` settings, blocking the calling thread
+pub struct BlockingTimetableGetter {
+    // Downloads in progress keep using the client they started with
+    client: Mutex<Arc<HttpClient>>,
+    // Keyed by URL
+    cache: Mutex<HashMap<String, CachedTimetable>>,
+    // Where `cache` is kept between runs, when set
+    cache_file: Option<PathBuf>,
+    // Why `cache` couldn't be kept in `cache_file`, until it's taken by `take_warning`
+    cache_error: Mutex<Option<String>>
+}
+impl BlockingTimetableGetter {
+    /// Fails when the proxy or certificate of `config` can't be used
+    pub fn new(config: HttpConfig) -> Result<Self, HttpConfigError> {
+        Ok(Self {
+            client: Mutex::new(Arc::new(HttpClient::new(config)?)),
+            cache: Mutex::new(HashMap::new()),
+            cache_file: None,
+            cache_error: Mutex::new(None)
+        })
+    }
+
+    /// Loads the cache from `filename` and saves it there after every download
+    pub fn with_cache_file(mut self, filename: PathBuf) -> Self {
+        self.cache = Mutex::new(read_cache(&filename));
+        self.cache_file = Some(filename);
+        self
+    }
+}
+impl Default for BlockingTimetableGetter {
+    fn default() -> Self {
+        Self::new(HttpConfig::default()).expect("Default HTTP config is valid")
+    }
+}
 impl TimetableGetter for BlockingTimetableGetter {
     fn get(&self, vidko: &str) -> Result<Timetable, GetTimetableError> {
-        fn find_property<'a>(properties: &'a [Property], name: &str) -> Result<&'a Property, GetTimetableError> {
-            for prop in properties {
-                if prop.name == name {
-                    return Ok(prop);
-                }
+        // Locks are not held while downloading, so that other downloads and settings changes don't wait
+        let client = self.client.lock().unwrap().clone();
+        let url = client.config.url(vidko);
+        let validators = self.cache.lock().unwrap()
+            .get(&url)
+            .map_or(Validators::default(), |cached| cached.validators.clone());
+        let mut resp = client.fetch(&url, &validators)?;
+
+        if resp.status() == 304 {
+            if let Some(cached) = self.cache.lock().unwrap().get(&url) {
+                return Ok(cached.timetable.clone());
             }
-            panic!("Property '{}' not found", name);
-        }
-
-        let resp = ureq::get(&format!("https://uais.cr.ktu.lt/ktuis/tv_rprt2.ical1?p={}&t=basic.ics", vidko))
-            .call()
-            .map_err(|_| GetTimetableError::NotFound)?;
-
-        let mut reader = ical::IcalParser::new(BufReader::new(resp.into_reader()));
-        let cal = reader.next();
-        if cal.is_none() {
-            return Err(GetTimetableError::NotFound)
-        }
-        let cal = cal.unwrap().unwrap();
-        if cal.events.is_empty() {
-            return Err(GetTimetableError::EmptyTimetable);
-        }
-
-        let mut timetable = Timetable { events: vec![] };
-        for event in cal.events {
-            let category_prop = find_property(&event.properties, "CATEGORIES")?;
-            let start_prop = find_property(&event.properties, "DTSTART")?;
-            let end_prop = find_property(&event.properties, "DTEND")?;
-            let description_prop = find_property(&event.properties, "DESCRIPTION")?;
-            let summary_prop = find_property(&event.properties, "SUMMARY")?;
-            let location_prop = find_property(&event.properties, "LOCATION")?;
-
-            let mut category = EventCategory::Default;
-            if let Some(category_str) = &category_prop.value {
-                category = match category_str.as_str() {
-                    "Yellow Category" => EventCategory::Yellow,
-                    "Green Category" => EventCategory::Green,
-                    "Red Category" => EventCategory::Red,
-                    "Blue Category" => EventCategory::Blue,
-                    _ => EventCategory::Default
-                };
+            // Nothing to keep, as the cached timetable is gone, so the whole timetable is asked for
+            resp = client.fetch(&url, &Validators::default())?;
+            if resp.status() == 304 {
+                return Err(GetTimetableError::RequestFailed("Not Modified without a cached timetable".into()));
             }
-            let start_str = start_prop.value.clone().unwrap();
-            let end_str = end_prop.value.clone().unwrap();
-            let (start_date, start_time) = start_str.split_once('T').unwrap();
-            let (_end_date, end_time) = end_str.split_once('T').unwrap();
-            let summary = summary_prop.value.clone().unwrap();
-
-            timetable.events.push(Event {
-                category,
-                date: NaiveDate::parse_from_str(start_date, "%Y%m%d").unwrap(),
-                start_time: NaiveTime::parse_from_str(start_time, "%H%M%S").unwrap(),
-                end_time: NaiveTime::parse_from_str(end_time, "%H%M%S").unwrap(),
-                description: description_prop.value.clone().unwrap(),
-                module_name: guess_module_name(&summary),
-                summary,
-                location: location_prop.value.clone().unwrap()
-            })
         }
 
-        timetable.events.sort_by_key(|event| (event.date, event.start_time));
+        let validators = Validators {
+            etag: resp.header("ETag").map(String::from),
+            last_modified: resp.header("Last-Modified").map(String::from)
+        };
+        let timetable = parse_timetable(BufReader::new(resp.into_reader()))?;
+
+        let mut cache = self.cache.lock().unwrap();
+        cache.insert(url, CachedTimetable { validators, timetable: timetable.clone() });
+        // The timetable was downloaded fine, so failing to cache it is not an error of `get`
+        if let Some(filename) = &self.cache_file {
+            if let Err(e) = write_cache(filename, &cache) {
+                *self.cache_error.lock().unwrap() = Some(format!("Failed to save the timetable cache: {}", e));
+            }
+        }
 
         Ok(timetable)
     }
+
+    fn cached(&self, vidko: &str) -> Option<Timetable> {
+        let url = self.client.lock().unwrap().config.url(vidko);
+        self.cache.lock().unwrap()
+            .get(&url)
+            .map(|cached| cached.timetable.clone())
+    }
+
+    fn take_warning(&self) -> Option<String> {
+        self.cache_error.lock().unwrap().take()
+    }
+
+    fn set_http_config(&self, config: &HttpConfig) -> Result<(), HttpConfigError> {
+        let client = HttpClient::new(config.clone())?;
+        *self.client.lock().unwrap() = Arc::new(client);
+        Ok(())
+    }
 }
 
+/// Gives the same timetable for every vidko code, for tests and trying the app without a network
 pub struct DummyTimetableGetter {
     timetable: Timetable
 }
 impl DummyTimetableGetter {
+    /// Every vidko code gets `timetable`
     #[allow(unused)]
     pub fn new(timetable: Timetable) -> Self {
         Self { timetable }
@@ -158,4 +788,214 @@ impl TimetableGetter for DummyTimetableGetter {
     fn get(&self, _vidko: &str) -> Result<Timetable, GetTimetableError> {
         Ok(self.timetable.clone())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::{io::{Read, Write}, net::TcpListener};
+
+    use crate::test_utils::{at, TempDir};
+
+    use super::*;
+
+    // Wraps VEVENT lines into a calendar
+    fn calendar(events: &[&[&str]]) -> String {
+        let mut ics = String::from("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Test//EN\r\n");
+        for (i, lines) in events.iter().enumerate() {
+            ics.push_str("BEGIN:VEVENT\r\n");
+            if !lines.iter().any(|line| line.starts_with("UID")) {
+                ics.push_str(&format!("UID:event-{}\r\n", i));
+            }
+            for line in lines.iter() {
+                ics.push_str(line);
+                ics.push_str("\r\n");
+            }
+            ics.push_str("SUMMARY:P175B123 Test\r\nEND:VEVENT\r\n");
+        }
+        ics.push_str("END:VCALENDAR\r\n");
+        ics
+    }
+
+    fn parse(events: &[&[&str]]) -> Result<Timetable, GetTimetableError> {
+        parse_timetable(calendar(events).as_bytes())
+    }
+
+    // Answers every connection with the next response, returns the URL template of the server
+    fn serve(responses: Vec<String>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for (response, stream) in responses.into_iter().zip(listener.incoming()) {
+                let mut stream = stream.unwrap();
+                let mut request = [0; 4096];
+                let _ = stream.read(&mut request);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        format!("http://{}/{{vidko}}", address)
+    }
+
+    fn response(status: &str, headers: &str, body: &str) -> String {
+        format!("HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: {}\r\n{}\r\n{}", status, body.len(), headers, body)
+    }
+
+    fn getter(url_template: String) -> BlockingTimetableGetter {
+        BlockingTimetableGetter::new(HttpConfig { url_template, retries: 0, ..HttpConfig::default() }).unwrap()
+    }
+
+    fn starts(timetable: &Timetable) -> Vec<NaiveDateTime> {
+        timetable.events.iter().map(|event| event.start).collect()
+    }
+
+    #[test]
+    fn converts_utc_times() {
+        let timetable = parse(&[&["DTSTART:20230201T070000Z", "DTEND:20230201T083000Z"]]).unwrap();
+        assert_eq!(timetable.events[0].start, at(2023, 2, 1, 9, 0));
+        assert_eq!(timetable.events[0].end, at(2023, 2, 1, 10, 30));
+
+        let timetable = parse(&[&["DTSTART;TZID=UTC:20230701T070000", "DTEND;TZID=Etc/UTC:20230701T083000"]]).unwrap();
+        assert_eq!(timetable.events[0].start, at(2023, 7, 1, 10, 0));
+        assert_eq!(timetable.events[0].end, at(2023, 7, 1, 11, 30));
+    }
+
+    #[test]
+    fn keeps_vilnius_and_floating_times() {
+        let timetable = parse(&[
+            &["DTSTART;TZID=Europe/Vilnius:20230201T090000", "DTEND;TZID=Europe/Vilnius:20230201T103000"],
+            &["DTSTART:20230202T090000", "DTEND:20230202T103000"]
+        ]).unwrap();
+        assert_eq!(starts(&timetable), vec![at(2023, 2, 1, 9, 0), at(2023, 2, 2, 9, 0)]);
+    }
+
+    #[test]
+    fn applies_exdate_and_rdate() {
+        let timetable = parse(&[&[
+            "DTSTART:20230201T090000",
+            "DTEND:20230201T103000",
+            "RRULE:FREQ=WEEKLY;COUNT=5",
+            "EXDATE:20230208T090000",
+            "EXDATE;VALUE=DATE:20230222",
+            "RDATE:20230210T090000,20230211T090000"
+        ]]).unwrap();
+        assert_eq!(starts(&timetable), vec![
+            at(2023, 2, 1, 9, 0), at(2023, 2, 10, 9, 0), at(2023, 2, 11, 9, 0), at(2023, 2, 15, 9, 0), at(2023, 3, 1, 9, 0)
+        ]);
+        assert!(timetable.events.iter().all(|event| event.end - event.start == Duration::minutes(90)));
+    }
+
+    #[test]
+    fn recurrence_id_replaces_occurrence() {
+        let timetable = parse(&[
+            &["UID:lecture", "DTSTART:20230201T090000", "DTEND:20230201T103000", "RRULE:FREQ=WEEKLY;COUNT=3"],
+            &["UID:lecture", "RECURRENCE-ID:20230208T090000", "DTSTART:20230209T120000", "DTEND:20230209T133000"]
+        ]).unwrap();
+        assert_eq!(starts(&timetable), vec![at(2023, 2, 1, 9, 0), at(2023, 2, 9, 12, 0), at(2023, 2, 15, 9, 0)]);
+    }
+
+    #[test]
+    fn uses_duration_without_dtend() {
+        let timetable = parse(&[
+            &["DTSTART:20230201T090000", "DURATION:PT1H30M"],
+            &["DTSTART;VALUE=DATE:20230202"],
+            &["DTSTART:20230203T090000"]
+        ]).unwrap();
+        let ends: Vec<NaiveDateTime> = timetable.events.iter().map(|event| event.end).collect();
+        assert_eq!(ends, vec![at(2023, 2, 1, 10, 30), at(2023, 2, 3, 0, 0), at(2023, 2, 3, 9, 0)]);
+    }
+
+    #[test]
+    fn utc_events_repeat_in_utc() {
+        // Clocks are turned forward on 2023-03-26, so the same UTC time is an hour later in Vilnius
+        let timetable = parse(&[&["DTSTART:20230320T070000Z", "DTEND:20230320T083000Z", "RRULE:FREQ=WEEKLY;COUNT=2"]]).unwrap();
+        assert_eq!(starts(&timetable), vec![at(2023, 3, 20, 9, 0), at(2023, 3, 27, 10, 0)]);
+    }
+
+    #[test]
+    fn huge_interval_does_not_panic() {
+        let timetable = parse(&[&["DTSTART:20230201T090000", "DTEND:20230201T103000", "RRULE:FREQ=YEARLY;INTERVAL=4294967295"]]).unwrap();
+        assert_eq!(starts(&timetable), vec![at(2023, 2, 1, 9, 0)]);
+    }
+
+    #[test]
+    fn rejects_other_timezones() {
+        let result = parse(&[&["DTSTART;TZID=America/New_York:20230201T090000", "DTEND;TZID=America/New_York:20230201T103000"]]);
+        assert!(matches!(result, Err(GetTimetableError::UnsupportedTimezone(tzid)) if tzid == "America/New_York"));
+    }
+
+    #[test]
+    fn not_modified_without_a_cached_timetable_downloads_it_again() {
+        let body = calendar(&[&["DTSTART:20230201T090000", "DTEND:20230201T103000"]]);
+        let url_template = serve(vec![
+            response("304 Not Modified", "", ""),
+            response("200 OK", "ETag: \"abc\"\r\n", &body)
+        ]);
+        let getter = getter(url_template);
+
+        let timetable = getter.get("E1810").unwrap();
+        assert_eq!(starts(&timetable), vec![at(2023, 2, 1, 9, 0)]);
+        assert_eq!(getter.cached("E1810").map(|timetable| timetable.events.len()), Some(1));
+    }
+
+    #[test]
+    fn failing_to_save_the_cache_is_a_warning() {
+        let directory = TempDir::new("blocked-cache");
+        let blocker = directory.path().join("blocker");
+        fs::write(&blocker, "").unwrap();
+        let body = calendar(&[&["DTSTART:20230201T090000", "DTEND:20230201T103000"]]);
+        let getter = getter(serve(vec![response("200 OK", "", &body)]))
+            .with_cache_file(blocker.join("timetable_cache.json"));
+
+        assert!(getter.get("E1810").is_ok());
+        assert!(getter.take_warning().is_some());
+        assert!(getter.take_warning().is_none());
+    }
+
+    #[test]
+    fn validates_vidko_codes() {
+        assert_eq!(normalize_vidko(" e1810 "), "E1810");
+        assert!(is_valid_vidko("E1810"));
+        assert!(is_valid_vidko("IFF0"));
+        assert!(!is_valid_vidko(""));
+        assert!(!is_valid_vidko("E18 10"));
+        assert!(!is_valid_vidko("E1810&t=x"));
+        assert!(!is_valid_vidko(&"E".repeat(17)));
+    }
+
+    #[test]
+    fn retries_only_errors_which_could_go_away() {
+        let status = |code| ureq::Error::Status(code, ureq::Response::new(code, "", "").unwrap());
+        assert!(is_transient(&status(503)));
+        assert!(is_transient(&status(429)));
+        assert!(!is_transient(&status(404)));
+        assert!(!is_transient(&ureq::get("not a url").call().unwrap_err()));
+        assert!(!is_transient(&ureq::get("gopher://example.com").call().unwrap_err()));
+    }
+
+    #[test]
+    fn cache_file_round_trip() {
+        let directory = TempDir::new("cache");
+        let filename = directory.path().join("timetable_cache.json");
+        let timetable = parse(&[&["DTSTART:20230201T090000", "DTEND:20230201T103000"]]).unwrap();
+        let mut cache = HashMap::new();
+        cache.insert("https://example.com/E1810".to_string(), CachedTimetable {
+            validators: Validators { etag: Some("\"abc\"".into()), last_modified: None },
+            timetable
+        });
+
+        write_cache(&filename, &cache).unwrap();
+        let read = read_cache(&filename);
+
+        let cached = &read["https://example.com/E1810"];
+        assert_eq!(cached.validators.etag.as_deref(), Some("\"abc\""));
+        assert_eq!(cached.validators.last_modified, None);
+        assert_eq!(starts(&cached.timetable), vec![at(2023, 2, 1, 9, 0)]);
+    }
+
+    #[test]
+    fn unreadable_cache_file_is_empty() {
+        let directory = TempDir::new("bad-cache");
+        let filename = directory.path().join("timetable_cache.json");
+        fs::write(&filename, "not json").unwrap();
+
+        assert!(read_cache(&filename).is_empty());
+    }
+}
@@ -0,0 +1,112 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday};
+
+// KTU timetables are in Europe/Vilnius time. Lithuania follows the EU daylight saving rules:
+// EEST (UTC+3) from the last sunday of march 01:00 UTC until the last sunday of october 01:00 UTC,
+// EET (UTC+2) otherwise.
+const STANDARD_OFFSET: i32 = 2 * 3600;
+const SUMMER_OFFSET: i32 = 3 * 3600;
+
+fn last_sunday(year: i32, month: u32) -> NaiveDate {
+    let next_month = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)
+    };
+    let mut date = next_month.expect("Invalid year or month given") - Duration::days(1);
+    while date.weekday() != Weekday::Sun {
+        date -= Duration::days(1);
+    }
+    date
+}
+
+fn is_summer_time(utc: NaiveDateTime) -> bool {
+    let switch_time = NaiveTime::from_hms_opt(1, 0, 0).unwrap();
+    let summer_start = last_sunday(utc.year(), 3).and_time(switch_time);
+    let summer_end = last_sunday(utc.year(), 10).and_time(switch_time);
+    summer_start <= utc && utc < summer_end
+}
+
+/// Offset from UTC in Vilnius at the UTC time `utc`
+pub fn vilnius_offset(utc: NaiveDateTime) -> FixedOffset {
+    let seconds = if is_summer_time(utc) { SUMMER_OFFSET } else { STANDARD_OFFSET };
+    FixedOffset::east_opt(seconds).unwrap()
+}
+
+/// Converts a UTC time into Vilnius time
+pub fn utc_to_vilnius(utc: DateTime<Utc>) -> NaiveDateTime {
+    let utc = utc.naive_utc();
+    utc + Duration::seconds(vilnius_offset(utc).local_minus_utc() as i64)
+}
+
+/// Times which happen twice when clocks are turned back are treated as summer time,
+/// skipped times when clocks are turned forward are treated as standard time.
+pub fn vilnius_to_utc(local: NaiveDateTime) -> DateTime<Utc> {
+    let summer_utc = local - Duration::seconds(SUMMER_OFFSET as i64);
+    let utc = if is_summer_time(summer_utc) {
+        summer_utc
+    } else {
+        local - Duration::seconds(STANDARD_OFFSET as i64)
+    };
+    DateTime::<Utc>::from_utc(utc, Utc)
+}
+
+/// Current time in Vilnius, regardless of the timezone set on this computer
+pub fn vilnius_now() -> NaiveDateTime {
+    utc_to_vilnius(Utc::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::at;
+
+    use super::*;
+
+    fn utc(datetime: NaiveDateTime) -> DateTime<Utc> {
+        DateTime::<Utc>::from_utc(datetime, Utc)
+    }
+
+    #[test]
+    fn finds_last_sundays() {
+        assert_eq!(last_sunday(2023, 3), NaiveDate::from_ymd_opt(2023, 3, 26).unwrap());
+        assert_eq!(last_sunday(2023, 10), NaiveDate::from_ymd_opt(2023, 10, 29).unwrap());
+        // March 2024 ends on a sunday
+        assert_eq!(last_sunday(2024, 3), NaiveDate::from_ymd_opt(2024, 3, 31).unwrap());
+        assert_eq!(last_sunday(2023, 12), NaiveDate::from_ymd_opt(2023, 12, 31).unwrap());
+    }
+
+    #[test]
+    fn switches_to_summer_time_in_march() {
+        // Clocks go from 03:00 to 04:00 at 01:00 UTC
+        assert_eq!(utc_to_vilnius(utc(at(2023, 3, 26, 0, 59))), at(2023, 3, 26, 2, 59));
+        assert_eq!(utc_to_vilnius(utc(at(2023, 3, 26, 1, 0))), at(2023, 3, 26, 4, 0));
+        assert_eq!(vilnius_to_utc(at(2023, 3, 26, 2, 59)), utc(at(2023, 3, 26, 0, 59)));
+        assert_eq!(vilnius_to_utc(at(2023, 3, 26, 4, 0)), utc(at(2023, 3, 26, 1, 0)));
+    }
+
+    #[test]
+    fn nonexistent_hour_is_standard_time() {
+        // 03:30 doesn't exist on that day
+        assert_eq!(vilnius_to_utc(at(2023, 3, 26, 3, 30)), utc(at(2023, 3, 26, 1, 30)));
+    }
+
+    #[test]
+    fn switches_to_standard_time_in_october() {
+        // Clocks go from 04:00 back to 03:00 at 01:00 UTC
+        assert_eq!(utc_to_vilnius(utc(at(2023, 10, 29, 0, 59))), at(2023, 10, 29, 3, 59));
+        assert_eq!(utc_to_vilnius(utc(at(2023, 10, 29, 1, 0))), at(2023, 10, 29, 3, 0));
+        assert_eq!(vilnius_to_utc(at(2023, 10, 29, 2, 30)), utc(at(2023, 10, 28, 23, 30)));
+        assert_eq!(vilnius_to_utc(at(2023, 10, 29, 4, 0)), utc(at(2023, 10, 29, 2, 0)));
+    }
+
+    #[test]
+    fn ambiguous_hour_is_summer_time() {
+        // 03:30 happens twice on that day, first at 00:30 UTC and then at 01:30 UTC
+        assert_eq!(vilnius_to_utc(at(2023, 10, 29, 3, 30)), utc(at(2023, 10, 29, 0, 30)));
+    }
+
+    #[test]
+    fn uses_standard_offset_in_winter() {
+        assert_eq!(utc_to_vilnius(utc(at(2023, 1, 15, 12, 0))), at(2023, 1, 15, 14, 0));
+        assert_eq!(utc_to_vilnius(utc(at(2023, 7, 15, 12, 0))), at(2023, 7, 15, 15, 0));
+    }
+}
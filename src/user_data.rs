@@ -0,0 +1,405 @@
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::{LoadConfigError, SaveConfigError, DEFAULT_PROFILE, data_dir, read_toml, write_toml, backup_file}, timetable::{Event, EventCategory}, i18n::Strings};
+
+/// Study sessions, team meetings and anything else the user adds themselves
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct PersonalEvent {
+    pub id: u64,
+    pub title: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    #[serde(default)]
+    pub location: String,
+    #[serde(default)]
+    pub description: String
+}
+
+/// Lab report deadlines and other assignments of a module
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct Task {
+    pub id: u64,
+    /// Same as `Event::module_label` of the module's events
+    pub module: String,
+    #[serde(default)]
+    pub module_code: Option<String>,
+    pub title: String,
+    pub due: NaiveDateTime,
+    #[serde(default)]
+    pub done: bool
+}
+
+/// Whether the user was at a past event
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Attendance {
+    Attended,
+    Missed,
+    Excused
+}
+
+impl Attendance {
+    /// In the order they are offered in the event details
+    pub const ALL: [Attendance; 3] = [Attendance::Attended, Attendance::Missed, Attendance::Excused];
+
+    /// Name of the state shown in the app
+    pub fn label(&self, strings: &Strings) -> &'static str {
+        strings.attendance_states[*self as usize]
+    }
+}
+
+/// Attendance of a single module over the whole semester, only past events are counted
+#[derive(Debug, Clone, Default)]
+pub struct AttendanceStats {
+    pub module: String,
+    pub attended: u32,
+    pub missed: u32,
+    pub excused: u32,
+    pub unmarked: u32
+}
+
+impl AttendanceStats {
+    /// Excused and unmarked events don't count towards the rate
+    pub fn rate(&self) -> Option<f32> {
+        let counted = self.attended + self.missed;
+        if counted == 0 {
+            return None;
+        }
+        Some(self.attended as f32 / counted as f32)
+    }
+}
+
+/// Everything the user adds on top of the official timetable
+#[derive(Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct UserData {
+    #[serde(default)]
+    pub events: Vec<PersonalEvent>,
+    #[serde(default)]
+    pub tasks: Vec<Task>,
+    /// Notes attached to official events, keyed by `Event::key`
+    #[serde(default)]
+    pub notes: BTreeMap<String, String>,
+    /// Also keyed by `Event::key`
+    #[serde(default)]
+    pub attendance: BTreeMap<String, Attendance>,
+    // Id given to the next event or task, never decreases so that removed ids (and their UIDs) aren't reused
+    #[serde(default)]
+    next_id: u64
+}
+
+impl PersonalEvent {
+    /// The event in the same form as official ones, with `personal_id` set
+    pub fn to_event(&self) -> Event {
+        Event {
+            category: EventCategory::Personal,
+            start: self.start,
+            end: self.end,
+            description: self.description.clone(),
+            summary: self.title.clone(),
+            location: self.location.clone(),
+            module_name: None,
+            module_code: None,
+            note: None,
+            attendance: None,
+            personal_id: Some(self.id)
+        }
+    }
+}
+
+impl UserData {
+    // Files saved before `next_id` existed start it after the largest id in use
+    fn take_id(&mut self) -> u64 {
+        let used = self.events.iter().map(|e| e.id)
+            .chain(self.tasks.iter().map(|t| t.id))
+            .max()
+            .unwrap_or(0);
+        self.next_id = self.next_id.max(used + 1);
+        let id = self.next_id;
+        self.next_id += 1;
+        return id;
+    }
+
+    /// Personal event with this id
+    pub fn event(&self, id: u64) -> Option<&PersonalEvent> {
+        self.events.iter().find(|event| event.id == id)
+    }
+
+    /// Adds a new event or replaces the one with the same id, ids of new events should be 0
+    pub fn save_event(&mut self, mut event: PersonalEvent) {
+        if let Some(existing) = self.events.iter_mut().find(|e| e.id == event.id && event.id != 0) {
+            *existing = event;
+            return;
+        }
+
+        event.id = self.take_id();
+        self.events.push(event);
+    }
+
+    /// Does nothing when there is no event with this id
+    pub fn remove_event(&mut self, id: u64) {
+        self.events.retain(|event| event.id != id);
+    }
+
+    /// Gives the task a new id, tasks are kept sorted by their due time
+    pub fn add_task(&mut self, mut task: Task) {
+        task.id = self.take_id();
+        self.tasks.push(task);
+        self.tasks.sort_by_key(|t| t.due);
+    }
+
+    /// Does nothing when there is no task with this id
+    pub fn remove_task(&mut self, id: u64) {
+        self.tasks.retain(|task| task.id != id);
+    }
+
+    /// Marks a task as done or not done
+    pub fn set_task_done(&mut self, id: u64, done: bool) {
+        if let Some(task) = self.tasks.iter_mut().find(|task| task.id == id) {
+            task.done = done;
+        }
+    }
+
+    /// Note the user attached to an official event
+    pub fn note(&self, event: &Event) -> Option<&str> {
+        self.notes.get(&event.key()).map(|note| note.as_str())
+    }
+
+    /// An empty note removes it
+    pub fn set_note(&mut self, event: &Event, note: &str) {
+        let note = note.trim();
+        if note.is_empty() {
+            self.notes.remove(&event.key());
+        } else {
+            self.notes.insert(event.key(), note.to_string());
+        }
+    }
+
+    /// Personal events happening between `from` and `to`, in the same form as official ones
+    pub fn events_between(&self, from: NaiveDateTime, to: NaiveDateTime) -> Vec<Event> {
+        self.events.iter()
+            .map(|event| event.to_event())
+            .filter(|event| event.overlaps(from, to))
+            .collect()
+    }
+
+    /// Whether the user marked being at an official event
+    pub fn attendance(&self, event: &Event) -> Option<Attendance> {
+        self.attendance.get(&event.key()).copied()
+    }
+
+    /// None removes the mark
+    pub fn set_attendance(&mut self, event: &Event, attendance: Option<Attendance>) {
+        match attendance {
+            Some(attendance) => self.attendance.insert(event.key(), attendance),
+            None => self.attendance.remove(&event.key())
+        };
+    }
+
+    /// Fills in the notes and attendance of official events
+    pub fn annotate_events(&self, events: &mut [Event]) {
+        for event in events {
+            if event.personal_id.is_none() {
+                event.note = self.note(event).map(|note| note.to_string());
+                event.attendance = self.attendance(event);
+            }
+        }
+    }
+
+    /// Statistics of every module which had an event before `now`, sorted by module
+    pub fn attendance_stats<'a>(&self, events: impl IntoIterator<Item = &'a Event>, now: NaiveDateTime) -> Vec<AttendanceStats> {
+        let mut stats: BTreeMap<&str, AttendanceStats> = BTreeMap::new();
+        for event in events {
+            if event.end > now || event.personal_id.is_some() {
+                continue;
+            }
+
+            let module = event.module_label();
+            let module_stats = stats.entry(module).or_insert_with(|| AttendanceStats {
+                module: module.to_string(),
+                ..AttendanceStats::default()
+            });
+            match self.attendance(event) {
+                Some(Attendance::Attended) => module_stats.attended += 1,
+                Some(Attendance::Missed) => module_stats.missed += 1,
+                Some(Attendance::Excused) => module_stats.excused += 1,
+                None => module_stats.unmarked += 1
+            }
+        }
+        stats.into_values().collect()
+    }
+}
+
+/// Where the user data is loaded from and saved to, every profile has its own
+pub trait UserDataStore {
+    /// `LoadConfigError::NotFound` when the profile has no user data yet
+    fn load(&self, profile: &str) -> Result<UserData, LoadConfigError>;
+    /// Replaces the stored user data of `profile`
+    fn save(&self, profile: &str, data: &UserData) -> Result<(), SaveConfigError>;
+}
+
+/// Kept beside `config.toml`. The default profile uses "user_data.toml", which was there before profiles,
+/// the others have a file each in the "profiles" directory.
+pub struct TomlUserDataStore {
+    directory: PathBuf
+}
+impl TomlUserDataStore {
+    fn new(directory: &Path) -> Self {
+        Self {
+            directory: directory.into()
+        }
+    }
+
+    fn filename(&self, profile: &str) -> PathBuf {
+        if profile == DEFAULT_PROFILE {
+            return self.directory.join("user_data.toml");
+        }
+        self.directory.join("profiles").join(profile_file_name(profile))
+    }
+}
+
+// Profile names can contain anything. Lowercase letters and digits are kept, every other character is written
+// as its hex code, so that different names never share a file, even on case insensitive file systems.
+fn profile_file_name(profile: &str) -> String {
+    let mut name = String::from("profile-");
+    for c in profile.chars() {
+        if c.is_ascii_lowercase() || c.is_ascii_digit() {
+            name.push(c);
+        } else {
+            name.push_str(&format!("_{:x}_", c as u32));
+        }
+    }
+    name.push_str(".toml");
+    return name;
+}
+
+impl Default for TomlUserDataStore {
+    fn default() -> Self {
+        Self::new(&data_dir())
+    }
+}
+impl UserDataStore for TomlUserDataStore {
+    fn load(&self, profile: &str) -> Result<UserData, LoadConfigError> {
+        let filename = self.filename(profile);
+        if !filename.exists() {
+            return Err(LoadConfigError::NotFound);
+        }
+        read_toml(&filename).map_err(|e| match backup_file(&filename) {
+            Some(backup) => LoadConfigError::BackedUp(Box::new(e), backup),
+            None => e
+        })
+    }
+
+    fn save(&self, profile: &str, data: &UserData) -> Result<(), SaveConfigError> {
+        write_toml(&self.filename(profile), data)
+    }
+}
+
+/// Gives the same data to every profile
+pub struct MemoryUserDataStore {
+    data: Option<UserData>
+}
+impl MemoryUserDataStore {
+    /// Every profile loads `data`, saving does nothing
+    #[allow(unused)]
+    pub fn new(data: UserData) -> Self {
+        Self { data: Some(data) }
+    }
+}
+impl UserDataStore for MemoryUserDataStore {
+    fn load(&self, _profile: &str) -> Result<UserData, LoadConfigError> {
+        self.data.clone().ok_or(LoadConfigError::NotFound)
+    }
+
+    fn save(&self, _profile: &str, _data: &UserData) -> Result<(), SaveConfigError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate};
+
+    use crate::{test_utils::at, timetable::EventCategory};
+
+    use super::*;
+
+    fn lecture(location: &str) -> Event {
+        let start = at(2023, 2, 6, 8, 30);
+        Event {
+            category: EventCategory::Yellow,
+            start,
+            end: start + Duration::minutes(90),
+            description: "Lecture".to_string(),
+            summary: "P175B123 Databases".to_string(),
+            location: location.to_string(),
+            module_name: Some("Databases".to_string()),
+            module_code: Some("P175B123".to_string()),
+            note: None,
+            attendance: None,
+            personal_id: None
+        }
+    }
+
+    fn task(title: &str) -> Task {
+        Task {
+            id: 0,
+            module: "Module".to_string(),
+            module_code: None,
+            title: title.to_string(),
+            due: NaiveDate::from_ymd_opt(2023, 3, 1).unwrap().and_hms_opt(12, 0, 0).unwrap(),
+            done: false
+        }
+    }
+
+    #[test]
+    fn removed_task_ids_are_not_reused() {
+        let mut data = UserData::default();
+        data.add_task(task("first"));
+        data.add_task(task("second"));
+        data.remove_task(2);
+        data.add_task(task("third"));
+
+        let ids: Vec<u64> = data.tasks.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![1, 3]);
+    }
+
+    #[test]
+    fn ids_continue_after_files_without_a_counter() {
+        let mut data: UserData = toml::from_str(r#"
+            [[tasks]]
+            id = 7
+            module = "Module"
+            title = "old"
+            due = "2023-03-01T12:00:00"
+        "#).unwrap();
+        data.add_task(task("new"));
+
+        assert_eq!(data.tasks.last().unwrap().id, 8);
+    }
+
+    #[test]
+    fn profiles_have_separate_files() {
+        let store = TomlUserDataStore::new(Path::new("data"));
+        assert_eq!(store.filename(DEFAULT_PROFILE), Path::new("data").join("user_data.toml"));
+        assert_eq!(store.filename("ana"), Path::new("data").join("profiles").join("profile-ana.toml"));
+        assert_ne!(store.filename("Ana"), store.filename("ana"));
+        assert_ne!(store.filename("a b"), store.filename("a_b"));
+        assert_eq!(profile_file_name("../x"), "profile-_2e__2e__2f_x.toml");
+    }
+
+    #[test]
+    fn parallel_groups_keep_their_own_notes() {
+        let first = lecture("XI r.-101");
+        let second = lecture("XI r.-202");
+        let mut data = UserData::default();
+        data.set_note(&first, "Bring a laptop");
+        data.set_attendance(&second, Some(Attendance::Missed));
+
+        assert_eq!(data.note(&first), Some("Bring a laptop"));
+        assert_eq!(data.note(&second), None);
+        assert_eq!(data.attendance(&first), None);
+        assert_eq!(data.attendance(&second), Some(Attendance::Missed));
+    }
+}
@@ -1,12 +1,167 @@
-use egui::ColorImage;
-
-pub fn load_image_from_memory(image_data: &[u8]) -> Result<ColorImage, image::ImageError> {
-    let image = image::load_from_memory(image_data)?;
-    let size = [image.width() as _, image.height() as _];
-    let image_buffer = image.to_rgba8();
-    let pixels = image_buffer.as_flat_samples();
-    Ok(ColorImage::from_rgba_unmultiplied(
-        size,
-        pixels.as_slice(),
-    ))
-}
\ No newline at end of file
+use std::ops::Add;
+
+use chrono::{Datelike, Duration, IsoWeek, NaiveDate, NaiveDateTime, Timelike, Weekday};
+
+use crate::config::WeekRollover;
+
+/// Monday of `week`
+pub fn week_monday(week: IsoWeek) -> NaiveDate {
+    NaiveDate::from_isoywd_opt(week.year(), week.week(), Weekday::Mon).expect("Invalid week or year given")
+}
+
+/// `IsoWeek` comparisons also look at the calendar year of the date the week was taken from,
+/// so a week spanning New Year isn't always equal to itself. Its monday is the same though.
+pub fn same_week(a: IsoWeek, b: IsoWeek) -> bool {
+    week_monday(a) == week_monday(b)
+}
+
+/// The week shown as the current one at `now`, it's the next one after the rollover
+pub fn get_current_week(now: NaiveDateTime, rollover: &WeekRollover) -> IsoWeek {
+    if rollover.enabled {
+        let day = now.weekday().num_days_from_monday();
+        let rollover_day = rollover.weekday.num_days_from_monday();
+        if day > rollover_day || (day == rollover_day && now.hour() >= rollover.hour) {
+            return now.date().add(Duration::days(7)).iso_week();
+        }
+    }
+    now.iso_week()
+}
+
+/// Today, or the monday of the current week if it was already rolled over
+pub fn get_current_day(now: NaiveDateTime, rollover: &WeekRollover) -> NaiveDate {
+    let current_week = get_current_week(now, rollover);
+    if same_week(now.iso_week(), current_week) {
+        now.date()
+    } else {
+        week_monday(current_week)
+    }
+}
+
+// Amount of scrolling needed to switch a week, one notch of a mouse wheel
+const WEEK_SCROLL_THRESHOLD: f32 = 50.0;
+// Seconds without scrolling after which a partly scrolled amount is forgotten
+const WEEK_SCROLL_TIMEOUT: f64 = 1.0;
+
+/// Adds up scrolling until there is enough of it to switch a week. Mouse wheels scroll
+/// in notches with idle frames in between, touchpads scroll a little on every frame.
+#[derive(Default)]
+pub struct WeekScroll {
+    amount: f32,
+    last_scroll: f64
+}
+impl WeekScroll {
+    /// Takes the scrolled amount of a frame and the time in seconds, returns by how many weeks to move.
+    /// Scrolling down or to the left moves forward.
+    pub fn update(&mut self, delta: f32, time: f64) -> i32 {
+        if delta == 0.0 {
+            return 0;
+        }
+        if time - self.last_scroll > WEEK_SCROLL_TIMEOUT {
+            self.amount = 0.0;
+        }
+        self.last_scroll = time;
+
+        self.amount += delta;
+        if self.amount <= -WEEK_SCROLL_THRESHOLD {
+            self.amount = 0.0;
+            return 1;
+        }
+        if self.amount >= WEEK_SCROLL_THRESHOLD {
+            self.amount = 0.0;
+            return -1;
+        }
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::at;
+
+    use super::*;
+
+    // `IsoWeek` equality also compares internal flags of the calendar year, so weeks are compared by numbers
+    fn current_week(now: NaiveDateTime, rollover: &WeekRollover) -> (i32, u32) {
+        let week = get_current_week(now, rollover);
+        (week.year(), week.week())
+    }
+
+    fn rollover(weekday: Weekday, hour: u32) -> WeekRollover {
+        WeekRollover { enabled: true, weekday, hour }
+    }
+
+    #[test]
+    fn weeks_spanning_new_year_are_the_same() {
+        // Both are in 2025-W01, but taken from dates of different calendar years
+        let monday = NaiveDate::from_ymd_opt(2024, 12, 30).unwrap().iso_week();
+        let saturday = NaiveDate::from_ymd_opt(2025, 1, 4).unwrap().iso_week();
+        assert!(same_week(monday, saturday));
+        assert!(same_week(saturday, week_monday(saturday).iso_week()));
+        assert!(!same_week(monday, NaiveDate::from_ymd_opt(2025, 1, 6).unwrap().iso_week()));
+    }
+
+    #[test]
+    fn week_53_rolls_over_into_next_year() {
+        // 2020-12-31 is a Thursday in 2020-W53
+        let rollover = rollover(Weekday::Sat, 0);
+        assert_eq!(current_week(at(2020, 12, 31, 12, 0), &rollover), (2020, 53));
+        assert_eq!(current_week(at(2021, 1, 2, 0, 0), &rollover), (2021, 1));
+        assert_eq!(current_week(at(2021, 1, 4, 8, 0), &rollover), (2021, 1));
+    }
+
+    #[test]
+    fn new_year_rollover() {
+        // 2023-12-31 is a Sunday in 2023-W52, 2024-01-01 is a Monday in 2024-W01
+        let rollover = rollover(Weekday::Sat, 0);
+        assert_eq!(current_week(at(2023, 12, 31, 23, 59), &rollover), (2024, 1));
+        assert_eq!(current_week(at(2024, 1, 1, 0, 0), &rollover), (2024, 1));
+        assert_eq!(get_current_day(at(2023, 12, 31, 23, 59), &rollover), NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+    }
+
+    #[test]
+    fn disabled_rollover_keeps_current_week() {
+        let rollover = WeekRollover { enabled: false, ..rollover(Weekday::Sat, 0) };
+        assert_eq!(current_week(at(2021, 1, 2, 12, 0), &rollover), (2020, 53));
+        assert_eq!(current_week(at(2023, 12, 31, 23, 59), &rollover), (2023, 52));
+        assert_eq!(get_current_day(at(2023, 12, 31, 23, 59), &rollover), NaiveDate::from_ymd_opt(2023, 12, 31).unwrap());
+    }
+
+    #[test]
+    fn rollover_starts_at_exact_hour() {
+        // 2023-02-03 is a Friday in 2023-W05
+        let rollover = rollover(Weekday::Fri, 18);
+        assert_eq!(current_week(at(2023, 2, 3, 17, 59), &rollover), (2023, 5));
+        assert_eq!(current_week(at(2023, 2, 3, 18, 0), &rollover), (2023, 6));
+        assert_eq!(current_week(at(2023, 2, 2, 23, 59), &rollover), (2023, 5));
+    }
+
+    // Feeds one frame every 1/60 of a second, returns the total shift
+    fn scroll(week_scroll: &mut WeekScroll, deltas: &[f32]) -> i32 {
+        deltas.iter().enumerate()
+            .map(|(i, delta)| week_scroll.update(*delta, 10.0 + i as f64 / 60.0))
+            .sum()
+    }
+
+    #[test]
+    fn wheel_notches_switch_weeks() {
+        // A mouse wheel notch is 50 points, with idle frames in between
+        let mut week_scroll = WeekScroll::default();
+        assert_eq!(scroll(&mut week_scroll, &[-50.0, 0.0, 0.0, 0.0, -50.0, 0.0, 0.0]), 2);
+        assert_eq!(scroll(&mut week_scroll, &[50.0, 0.0, 0.0]), -1);
+    }
+
+    #[test]
+    fn swipes_add_up_across_frames() {
+        let mut week_scroll = WeekScroll::default();
+        assert_eq!(scroll(&mut week_scroll, &[-20.0, 0.0, -20.0, 0.0, 0.0, -5.0]), 0);
+        assert_eq!(week_scroll.update(-5.0, 10.2), 1);
+    }
+
+    #[test]
+    fn partial_scrolling_is_forgotten() {
+        let mut week_scroll = WeekScroll::default();
+        assert_eq!(week_scroll.update(-40.0, 10.0), 0);
+        assert_eq!(week_scroll.update(-40.0, 12.0), 0);
+        assert_eq!(week_scroll.update(-10.0, 12.5), 1);
+    }
+}